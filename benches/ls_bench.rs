@@ -0,0 +1,55 @@
+//! Times `ls` on directories of growing size, for a rough view of how the
+//! cost per entry behaves. Wall-clock time also covers sorting and process
+//! startup and varies between machines, so nothing is asserted here; the
+//! one-stat-per-entry guarantee is checked by `tests/ls_stat_count_test.rs`.
+//!
+//! Run with `cargo bench --bench ls_bench`.
+
+use std::fs;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use tempfile::tempdir;
+
+const SIZES: [usize; 4] = [5_000, 10_000, 20_000, 40_000];
+const RUNS: usize = 3;
+
+fn main() {
+    for flags in [&["-l"][..], &["-t"], &["-S"], &["-l", "-t"]] {
+        let mut per_entry = Vec::new();
+        for size in SIZES {
+            let dir = tempdir().unwrap();
+            for index in 0..size {
+                fs::write(dir.path().join(format!("file{:06}", index)), "").unwrap();
+            }
+
+            let best = (0..RUNS).map(|_| time_ls(flags, dir.path())).min().unwrap();
+            let micros = best.as_secs_f64() * 1e6 / size as f64;
+            println!(
+                "ls {:<6} {:>6} entries: {:>8.1} ms, {:>6.2} us/entry",
+                flags.join(" "),
+                size,
+                best.as_secs_f64() * 1e3,
+                micros
+            );
+            per_entry.push(micros);
+        }
+
+        println!(
+            "ls {:<6} cost per entry grew {:.2}x",
+            flags.join(" "),
+            per_entry[per_entry.len() - 1] / per_entry[0]
+        );
+    }
+}
+
+fn time_ls(flags: &[&str], dir: &std::path::Path) -> Duration {
+    let start = Instant::now();
+    let status = Command::new(env!("CARGO_BIN_EXE_ls"))
+        .args(flags)
+        .arg(dir)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    start.elapsed()
+}
@@ -1,4 +1,12 @@
+use crate::color::ColorWhen;
+use crate::quote::QuotingStyle;
+use crate::record::OutputFormat;
+use crate::size::SizeFormat;
+use crate::sort::SortKey;
+use crate::time::{TimeField, TimeStyle};
+use crate::tree::Charset;
 use clap::Parser;
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(name = "ls", version = "0.1.0", about = "List directory contents")]
@@ -9,21 +17,269 @@ pub struct LsArgs {
     #[arg(short, long, help = "Use a long listing format")]
     pub long: bool,
 
-    #[arg(short = 'H', long, help = "With -l, print human readable sizes (e.g., 1K 234M 2G)")]
+    #[arg(
+        short = 'H',
+        long,
+        help = "With -l and -s, print human readable sizes (e.g., 1K 234M 2G)",
+        overrides_with_all = ["si", "block_size"]
+    )]
     pub human_readable: bool,
 
+    #[arg(
+        long,
+        help = "Like -H, but use powers of 1000, not 1024",
+        overrides_with_all = ["human_readable", "block_size"]
+    )]
+    pub si: bool,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = SizeFormat::parse,
+        help = "Scale sizes by SIZE, e.g. K, 1M, KB, KiB or '1 to group thousands",
+        overrides_with_all = ["human_readable", "si"]
+    )]
+    pub block_size: Option<SizeFormat>,
+
+    #[arg(
+        short = 's',
+        long,
+        help = "Print the allocated size of each file, in blocks"
+    )]
+    pub size: bool,
+
+    #[arg(
+        short = 'k',
+        long,
+        help = "Default to 1024-byte blocks for -s and total"
+    )]
+    pub kibibytes: bool,
+
     #[arg(short = 'r', long, help = "Reverse order while sorting")]
     pub reverse: bool,
 
-    #[arg(short = 't', long, help = "Sort by modification time, newest first")]
+    #[arg(
+        short = 't',
+        long,
+        help = "Sort by modification time, newest first",
+        overrides_with_all = ["sort_size", "sort_extension", "sort_version", "unsorted", "sort"]
+    )]
     pub sort_time: bool,
 
-    #[arg(short = 'S', help = "Sort by file size, largest first")]
+    #[arg(
+        short = 'S',
+        help = "Sort by file size, largest first",
+        overrides_with_all = ["sort_time", "sort_extension", "sort_version", "unsorted", "sort"]
+    )]
     pub sort_size: bool,
 
+    #[arg(
+        short = 'X',
+        help = "Sort alphabetically by extension",
+        overrides_with_all = ["sort_time", "sort_size", "sort_version", "unsorted", "sort"]
+    )]
+    pub sort_extension: bool,
+
+    #[arg(
+        short = 'v',
+        help = "Natural sort of version numbers within names",
+        overrides_with_all = ["sort_time", "sort_size", "sort_extension", "unsorted", "sort"]
+    )]
+    pub sort_version: bool,
+
+    #[arg(
+        short = 'U',
+        help = "Do not sort; list entries in directory order",
+        overrides_with_all = ["sort_time", "sort_size", "sort_extension", "sort_version", "sort"]
+    )]
+    pub unsorted: bool,
+
+    #[arg(
+        long,
+        value_name = "WORD",
+        value_enum,
+        help = "Sort by WORD instead of name: none, name, size, time, version or extension",
+        overrides_with_all = ["sort_time", "sort_size", "sort_extension", "sort_version", "unsorted"]
+    )]
+    pub sort: Option<SortKey>,
+
+    #[arg(long, help = "Group directories before files")]
+    pub group_directories_first: bool,
+
+    #[arg(
+        short = 'u',
+        help = "Show access times with -l; sort by them with -t or without -l",
+        overrides_with_all = ["change_time", "time"]
+    )]
+    pub access_time: bool,
+
+    #[arg(
+        short = 'c',
+        help = "Show status change times with -l; sort by them with -t or without -l",
+        overrides_with_all = ["access_time", "time"]
+    )]
+    pub change_time: bool,
+
+    #[arg(
+        long,
+        value_name = "WORD",
+        value_enum,
+        help = "Show and sort by WORD instead of the modification time: atime, ctime, birth or mtime",
+        overrides_with_all = ["access_time", "change_time"]
+    )]
+    pub time: Option<TimeField>,
+
+    #[arg(
+        long,
+        value_name = "TIME_STYLE",
+        value_parser = TimeStyle::parse,
+        help = "Show times with -l as full-iso, long-iso, iso, locale, relative or +FORMAT"
+    )]
+    pub time_style: Option<TimeStyle>,
+
+    #[arg(long, help = "Like -l --time-style=full-iso")]
+    pub full_time: bool,
+
     #[arg(short = 'R', long, help = "List subdirectories recursively")]
     pub recursive: bool,
 
-    #[arg(default_value = ".", help = "Path to list")]
-    pub path: String,
-}
\ No newline at end of file
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "recursive",
+        help = "With -R, descend at most N levels below each operand"
+    )]
+    pub max_depth: Option<usize>,
+
+    #[arg(
+        long,
+        help = "With -R or --tree, stay on the file system of each operand"
+    )]
+    pub one_file_system: bool,
+
+    #[arg(
+        short = 'L',
+        long,
+        help = "Show the file a symlink points to instead of the link, and follow linked directories with -R"
+    )]
+    pub dereference: bool,
+
+    #[arg(
+        long,
+        help = "Follow symlinks given on the command line (GNU ls spells this -H, which is --human-readable here)"
+    )]
+    pub dereference_command_line: bool,
+
+    #[arg(long, help = "List subdirectories recursively as a tree")]
+    pub tree: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        requires = "tree",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "With --tree, descend at most N levels"
+    )]
+    pub level: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "CHARSET",
+        value_enum,
+        default_value = "utf-8",
+        help = "Draw tree branches with utf-8 or ascii characters"
+    )]
+    pub charset: Charset,
+
+    #[arg(
+        short = 'C',
+        help = "List entries by columns (default when writing to a terminal)",
+        overrides_with_all = ["across", "one_per_line"]
+    )]
+    pub columns: bool,
+
+    #[arg(
+        short = 'x',
+        help = "List entries by lines instead of by columns",
+        overrides_with_all = ["columns", "one_per_line"]
+    )]
+    pub across: bool,
+
+    #[arg(
+        short = '1',
+        help = "List one file per line",
+        overrides_with_all = ["columns", "across"]
+    )]
+    pub one_per_line: bool,
+
+    #[arg(
+        long,
+        value_name = "WORD",
+        value_enum,
+        help = "Print every entry with all its metadata as json, ndjson or csv"
+    )]
+    pub format: Option<OutputFormat>,
+
+    #[arg(
+        short = 'w',
+        long,
+        value_name = "COLS",
+        help = "Set output width to COLS; 0 means no limit"
+    )]
+    pub width: Option<usize>,
+
+    #[arg(
+        long,
+        value_name = "WHEN",
+        value_enum,
+        default_value = "auto",
+        default_missing_value = "always",
+        num_args = 0..=1,
+        require_equals = true,
+        help = "Color file names from LS_COLORS: always, auto or never"
+    )]
+    pub color: ColorWhen,
+
+    #[arg(
+        long,
+        value_name = "WORD",
+        value_enum,
+        overrides_with_all = ["escape", "literal"],
+        help = "Quote names with WORD: literal, shell, shell-always, shell-escape, shell-escape-always, c, escape or locale"
+    )]
+    pub quoting_style: Option<QuotingStyle>,
+
+    #[arg(
+        short = 'b',
+        long,
+        help = "Print C-style escapes for non-printable characters",
+        overrides_with_all = ["quoting_style", "literal"]
+    )]
+    pub escape: bool,
+
+    #[arg(
+        short = 'N',
+        long,
+        help = "Print names without quoting",
+        overrides_with_all = ["quoting_style", "escape"]
+    )]
+    pub literal: bool,
+
+    #[arg(
+        short = 'q',
+        long,
+        help = "Print ? instead of non-printable characters",
+        overrides_with = "show_control_chars"
+    )]
+    pub hide_control_chars: bool,
+
+    #[arg(
+        long,
+        help = "Show non-printable characters as is",
+        overrides_with = "hide_control_chars"
+    )]
+    pub show_control_chars: bool,
+
+    #[arg(default_value = ".", help = "Files or directories to list")]
+    pub paths: Vec<PathBuf>,
+}
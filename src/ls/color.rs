@@ -0,0 +1,323 @@
+use clap::ValueEnum;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::io::{self, IsTerminal};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+/// When to color file names (--color)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ColorWhen {
+    Always,
+    Auto,
+    Never,
+}
+
+/// Every indicator key understood in LS_COLORS, with the built-in default
+/// used when the variable does not set it (same defaults as GNU ls)
+const INDICATORS: &[(&str, &str)] = &[
+    ("lc", "\x1b["),
+    ("rc", "m"),
+    ("ec", ""),
+    ("rs", "0"),
+    ("no", ""),
+    ("fi", ""),
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("pi", "33"),
+    ("so", "01;35"),
+    ("bd", "01;33"),
+    ("cd", "01;33"),
+    ("mi", ""),
+    ("or", ""),
+    ("ex", "01;32"),
+    ("do", "01;35"),
+    ("su", "37;41"),
+    ("sg", "30;43"),
+    ("st", "37;44"),
+    ("ow", "34;42"),
+    ("tw", "30;42"),
+    ("ca", ""),
+    ("mh", ""),
+    ("cl", "\x1b[K"),
+];
+
+/// Color database parsed from LS_COLORS
+pub struct Colors {
+    indicators: HashMap<&'static str, String>,
+    /// `*pattern=code` entries in the order they appear; later entries win
+    extensions: Vec<(String, String)>,
+    /// `ln=target`: color symlinks like the file they point to
+    link_as_target: bool,
+    /// GNU ls resets the terminal once before the first colored name
+    reset_sent: Cell<bool>,
+}
+
+impl Colors {
+    /// Build the color database for this run, or `None` when names should be
+    /// printed without color. `auto` colors only a terminal and honours `NO_COLOR`.
+    pub fn for_output(when: ColorWhen) -> Option<Self> {
+        let enabled = match when {
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+            ColorWhen::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        };
+        enabled.then(|| Self::parse(&std::env::var("LS_COLORS").unwrap_or_default()))
+    }
+
+    /// Parse an LS_COLORS string such as `di=01;34:ln=target:*.tar=01;31`.
+    /// Malformed entries are ignored, like GNU ls does.
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = Colors {
+            indicators: INDICATORS
+                .iter()
+                .map(|&(key, value)| (key, value.to_string()))
+                .collect(),
+            extensions: Vec::new(),
+            link_as_target: false,
+            reset_sent: Cell::new(false),
+        };
+
+        for entry in split_entries(spec) {
+            let Some((key, value)) = entry.split_once('=') else {
+                continue;
+            };
+            let Some(value) = unescape(value) else {
+                continue;
+            };
+            if let Some(pattern) = key.strip_prefix('*') {
+                colors.extensions.push((pattern.to_string(), value));
+            } else if key == "ln" && value == "target" {
+                colors.link_as_target = true;
+            } else if let Some(&(known, _)) = INDICATORS.iter().find(|(known, _)| *known == key) {
+                colors.indicators.insert(known, value);
+            }
+        }
+        colors
+    }
+
+    /// Wrap `text`, the quoted form of `name`, in the escape sequences for the
+    /// file at `path`, whose `lstat` result is `metadata`
+    pub fn paint(&self, path: &Path, metadata: &Metadata, name: &OsStr, text: &[u8]) -> Vec<u8> {
+        match self.code_for(path, metadata, name.as_bytes()) {
+            Some(code) => self.wrap(code, text),
+            None => text.to_vec(),
+        }
+    }
+
+    /// Wrap the target shown after `->` in the long format. As in GNU ls,
+    /// targets are only colored when `mi`, `or` or `ln=target` is set.
+    pub fn paint_target(&self, resolved: &Path, name: &OsStr, text: &[u8]) -> Vec<u8> {
+        if !self.link_as_target && self.indicator("mi").is_none() && self.indicator("or").is_none()
+        {
+            return text.to_vec();
+        }
+        let code = match crate::stat(resolved, true) {
+            Ok(target) => self.code_for_type(&target, name.as_bytes()),
+            Err(_) => self.indicator("mi"),
+        };
+        match code {
+            Some(code) => self.wrap(code, text),
+            None => text.to_vec(),
+        }
+    }
+
+    fn wrap(&self, code: &str, text: &[u8]) -> Vec<u8> {
+        let end = match self.indicators["ec"].as_str() {
+            "" => format!(
+                "{}{}{}",
+                self.indicators["lc"], self.indicators["rs"], self.indicators["rc"]
+            ),
+            end => end.to_string(),
+        };
+        let reset = if self.reset_sent.replace(true) {
+            String::new()
+        } else {
+            end.clone()
+        };
+        let mut out = format!(
+            "{}{}{}{}",
+            reset, self.indicators["lc"], code, self.indicators["rc"]
+        )
+        .into_bytes();
+        out.extend_from_slice(text);
+        out.extend_from_slice(end.as_bytes());
+        out
+    }
+
+    /// A non-empty indicator code; `0` and `00` mean "no color" as in GNU ls
+    fn indicator(&self, key: &str) -> Option<&str> {
+        self.indicators
+            .get(key)
+            .map(String::as_str)
+            .filter(|code| !code.is_empty() && !code.bytes().all(|b| b == b'0'))
+    }
+
+    fn code_for(&self, path: &Path, metadata: &Metadata, name: &[u8]) -> Option<&str> {
+        if metadata.file_type().is_symlink() {
+            return match crate::stat(path, true) {
+                Err(_) if self.link_as_target => self.indicator("or"),
+                Err(_) => self.indicator("or").or_else(|| self.indicator("ln")),
+                Ok(target) if self.link_as_target => self.code_for_type(&target, name),
+                Ok(_) => self.indicator("ln"),
+            };
+        }
+        self.code_for_type(metadata, name)
+    }
+
+    fn code_for_type(&self, metadata: &Metadata, name: &[u8]) -> Option<&str> {
+        let file_type = metadata.file_type();
+        let mode = metadata.mode();
+        let key = if file_type.is_dir() {
+            match (mode & 0o1000 != 0, mode & 0o002 != 0) {
+                (true, true) => "tw",
+                (false, true) => "ow",
+                (true, false) => "st",
+                (false, false) => "di",
+            }
+        } else if file_type.is_fifo() {
+            "pi"
+        } else if file_type.is_socket() {
+            "so"
+        } else if file_type.is_block_device() {
+            "bd"
+        } else if file_type.is_char_device() {
+            "cd"
+        } else if !file_type.is_file() {
+            "no"
+        } else if mode & 0o4000 != 0 && self.indicator("su").is_some() {
+            "su"
+        } else if mode & 0o2000 != 0 && self.indicator("sg").is_some() {
+            "sg"
+        } else if mode & 0o111 != 0 && self.indicator("ex").is_some() {
+            "ex"
+        } else if metadata.nlink() > 1 && self.indicator("mh").is_some() {
+            "mh"
+        } else {
+            return self
+                .extension_code(name)
+                .or_else(|| self.indicator("fi"))
+                .or_else(|| self.indicator("no"));
+        };
+        self.indicator(key).or_else(|| self.indicator("no"))
+    }
+
+    /// Match `*suffix` entries against the end of the name. An entry with the
+    /// exact case wins; otherwise the last ASCII case-insensitive match is used.
+    fn extension_code(&self, name: &[u8]) -> Option<&str> {
+        let matching = |exact: bool| {
+            self.extensions.iter().rev().find(|(pattern, _)| {
+                let pattern = pattern.as_bytes();
+                name.len() >= pattern.len() && {
+                    let suffix = &name[name.len() - pattern.len()..];
+                    if exact {
+                        suffix == pattern
+                    } else {
+                        suffix.eq_ignore_ascii_case(pattern)
+                    }
+                }
+            })
+        };
+        matching(true)
+            .or_else(|| matching(false))
+            .map(|(_, code)| code.as_str())
+            .filter(|code| !code.is_empty())
+    }
+}
+
+/// Split on `:` separators that are not escaped with a backslash
+fn split_entries(spec: &str) -> Vec<&str> {
+    let mut entries = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (index, c) in spec.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ':' => {
+                entries.push(&spec[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    entries.push(&spec[start..]);
+    entries
+        .into_iter()
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// Decode the escapes allowed in LS_COLORS values: `\a \b \e \f \n \r \t \v
+/// \? \_` (space), octal `\NNN`, hex `\xHH` and caret notation such as `^[`
+fn unescape(value: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escape = chars.next()?;
+                out.push(match escape {
+                    'a' => '\x07',
+                    'b' => '\x08',
+                    'e' => '\x1b',
+                    'f' => '\x0c',
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    'v' => '\x0b',
+                    '?' => '\x7f',
+                    '_' => ' ',
+                    'x' | 'X' => {
+                        let mut code = 0;
+                        let mut digits = 0;
+                        while let Some(digit) = chars.peek().and_then(|d| d.to_digit(16)) {
+                            code = code * 16 + digit;
+                            digits += 1;
+                            chars.next();
+                            if digits == 2 {
+                                break;
+                            }
+                        }
+                        if digits == 0 {
+                            return None;
+                        }
+                        char::from_u32(code)?
+                    }
+                    '0'..='7' => {
+                        let mut code = escape.to_digit(8)?;
+                        for _ in 0..2 {
+                            match chars.peek().and_then(|d| d.to_digit(8)) {
+                                Some(digit) => {
+                                    code = code * 8 + digit;
+                                    chars.next();
+                                }
+                                None => break,
+                            }
+                        }
+                        char::from_u32(code & 0xff)?
+                    }
+                    other => other,
+                });
+            }
+            '^' => {
+                let caret = chars.next()?;
+                out.push(match caret {
+                    '?' => '\x7f',
+                    '@'..='_' | 'a'..='z' => {
+                        char::from_u32(caret.to_ascii_uppercase() as u32 & 0x1f)?
+                    }
+                    _ => return None,
+                });
+            }
+            _ => out.push(c),
+        }
+    }
+    Some(out)
+}
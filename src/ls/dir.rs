@@ -0,0 +1,58 @@
+use std::ffi::{CStr, CString, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+/// The names in a directory, in the order the file system returns them.
+/// Unlike `std::fs::read_dir`, `.` and `..` are included where `readdir`
+/// returns them, which -U with -a shows.
+pub struct DirStream {
+    dir: *mut libc::DIR,
+    /// Set after an error, which ends the stream
+    failed: bool,
+}
+
+impl DirStream {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let dir = unsafe { libc::opendir(path.as_ptr()) };
+        if dir.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(DirStream { dir, failed: false })
+    }
+}
+
+impl Iterator for DirStream {
+    type Item = io::Result<OsString>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        // readdir returns NULL both at the end and on errors; only errors set errno
+        unsafe {
+            *libc::__errno_location() = 0;
+            let entry = libc::readdir(self.dir);
+            if entry.is_null() {
+                let error = io::Error::last_os_error();
+                if error.raw_os_error() == Some(0) {
+                    return None;
+                }
+                self.failed = true;
+                return Some(Err(error));
+            }
+            let name = CStr::from_ptr((*entry).d_name.as_ptr());
+            Some(Ok(OsString::from_vec(name.to_bytes().to_vec())))
+        }
+    }
+}
+
+impl Drop for DirStream {
+    fn drop(&mut self) {
+        unsafe {
+            libc::closedir(self.dir);
+        }
+    }
+}
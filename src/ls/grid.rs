@@ -0,0 +1,109 @@
+use std::io::{self, IsTerminal};
+
+/// Spaces between two columns
+const COLUMN_GAP: usize = 2;
+/// Narrowest possible column: one character plus the gap
+const MIN_COLUMN_WIDTH: usize = 1 + COLUMN_GAP;
+
+/// A single name to be laid out. `width` is the display width on the terminal,
+/// which may differ from the length of `text` because of multibyte characters
+/// and color escapes.
+pub struct Cell {
+    pub text: Vec<u8>,
+    pub width: usize,
+}
+
+impl Cell {
+    pub fn new(text: Vec<u8>, width: usize) -> Self {
+        Cell { text, width }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// Fill columns top to bottom (-C)
+    Down,
+    /// Fill rows left to right (-x)
+    Across,
+}
+
+/// Width of the terminal attached to stdout, or `None` when stdout is not a terminal
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0 && size.ws_col > 0).then_some(size.ws_col as usize)
+}
+
+/// Lay out cells in as many columns as fit in `line_width`, using the same
+/// rules as GNU ls. A `line_width` of 0 means there is no limit.
+pub fn layout(cells: &[Cell], line_width: usize, direction: Direction) -> Vec<Vec<u8>> {
+    if cells.is_empty() {
+        return Vec::new();
+    }
+    let line_width = if line_width == 0 {
+        usize::MAX
+    } else {
+        line_width
+    };
+
+    let max_columns = (line_width / MIN_COLUMN_WIDTH).clamp(1, cells.len());
+    let (rows, widths) = (1..=max_columns)
+        .rev()
+        .map(|columns| column_widths(cells, columns, direction))
+        .find(|(_, widths)| widths.len() == 1 || widths.iter().sum::<usize>() < line_width)
+        .unwrap();
+
+    let columns = widths.len();
+    (0..rows)
+        .map(|row| {
+            let mut line = Vec::new();
+            for (column, width) in widths.iter().enumerate() {
+                let Some(cell) = cell_at(cells, rows, columns, row, column, direction) else {
+                    break;
+                };
+                line.extend_from_slice(&cell.text);
+                if cell_at(cells, rows, columns, row, column + 1, direction).is_some() {
+                    line.resize(line.len() + width - cell.width, b' ');
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+/// Compute the row count and the width of each column, including the gap after
+/// every column but the last, when using `columns` columns
+fn column_widths(cells: &[Cell], columns: usize, direction: Direction) -> (usize, Vec<usize>) {
+    let rows = cells.len().div_ceil(columns);
+    let mut widths = vec![MIN_COLUMN_WIDTH; columns];
+    for (index, cell) in cells.iter().enumerate() {
+        let column = match direction {
+            Direction::Down => index / rows,
+            Direction::Across => index % columns,
+        };
+        let gap = if column + 1 == columns { 0 } else { COLUMN_GAP };
+        widths[column] = widths[column].max(cell.width + gap);
+    }
+    (rows, widths)
+}
+
+fn cell_at(
+    cells: &[Cell],
+    rows: usize,
+    columns: usize,
+    row: usize,
+    column: usize,
+    direction: Direction,
+) -> Option<&Cell> {
+    if column >= columns {
+        return None;
+    }
+    let index = match direction {
+        Direction::Down => column * rows + row,
+        Direction::Across => row * columns + column,
+    };
+    cells.get(index)
+}
@@ -1,267 +1,989 @@
-use chrono::{DateTime, Local};
 use clap::Parser;
-use std::cmp::Ordering;
-use std::fs;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::{self, Metadata};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
 use std::path::{Path, PathBuf};
-use users::{get_group_by_gid, get_user_by_uid};
+use std::process;
+#[cfg(feature = "stat-count")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use users::{Groups, Users, UsersCache};
 
 mod args;
+mod color;
+mod dir;
+mod grid;
+mod quote;
+mod record;
+mod size;
+mod sort;
+mod time;
+mod tree;
+
+use args::LsArgs;
+use clap::ValueEnum;
+use color::Colors;
+use dir::DirStream;
+use grid::{Cell, Direction};
+use quote::{Quoting, QuotingStyle};
+use record::{Record, RecordWriter};
+use size::SizeFormat;
+use sort::SortKey;
+use time::{TimeField, TimeStyle};
+use tree::Counts;
+
+/// Exit status for minor problems, such as an entry that cannot be stat'ed or
+/// a directory read that fails partway
+const EXIT_MINOR: i32 = 1;
+/// Exit status for serious trouble, such as an operand that cannot be accessed
+const EXIT_SERIOUS: i32 = 2;
+
+/// Directories with fewer entries than this are stat'ed on the current thread
+const PARALLEL_STAT_THRESHOLD: usize = 1024;
+/// Threads used to stat large directories; stats on network file systems are
+/// bound by latency, so this does not follow the number of CPUs
+const STAT_THREADS: usize = 8;
+
+/// Size of the blocks counted in `st_blocks`
+const BLOCK_UNIT: u64 = 512;
+
+/// Number of `stat` calls made so far. Builds with the `stat-count` feature
+/// print it on exit when LS_COUNT_STATS is set, so tests can check that
+/// entries are stat'ed once.
+#[cfg(feature = "stat-count")]
+static STAT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// A name to list together with the path used to inspect it. Operands keep
+/// the name they were given on the command line.
+struct Entry {
+    path: PathBuf,
+    name: OsString,
+    /// `lstat` result, collected once per entry and shared by sorting,
+    /// coloring and the long format; `None` when the listing needs no metadata
+    metadata: Option<Metadata>,
+}
 
-fn main() -> anyhow::Result<()> {
-    let args = args::LsArgs::parse();
-    let path = Path::new(&args.path);
-
-    list_directory(path, &args, 0)?; // Start recursion with depth 0
+/// State shared by every listing in one run
+struct Lister<'a> {
+    args: &'a LsArgs,
+    colors: Option<Colors>,
+    quoting: Quoting,
+    /// How -l shows file sizes
+    size_format: SizeFormat,
+    /// How -s and `total` show allocated blocks
+    block_format: SizeFormat,
+    /// How -l shows timestamps
+    time_style: TimeStyle,
+    /// Set with --format, which prints records instead of listings
+    records: Option<RecordWriter>,
+    /// User and group names, looked up once per id
+    users: UsersCache,
+    /// Highest exit status reported so far
+    exit_code: i32,
+    /// Whether a listing has been printed, so the next one needs a blank line
+    printed: bool,
+    /// Device and inode of the directories being listed, to detect loops
+    active: HashSet<(u64, u64)>,
+    /// Device of the operand being listed, for --one-file-system
+    root_device: u64,
+}
 
-    Ok(())
+fn main() {
+    let mut args = LsArgs::parse();
+    args.long |= args.full_time;
+    sort::init_collation();
+    let mut lister = Lister {
+        args: &args,
+        colors: Colors::for_output(args.color),
+        quoting: name_quoting(&args),
+        size_format: size_format(&args),
+        block_format: block_format(&args),
+        time_style: time_style(&args),
+        records: args.format.map(RecordWriter::new),
+        users: UsersCache::new(),
+        exit_code: 0,
+        printed: false,
+        active: HashSet::new(),
+        root_device: 0,
+    };
+
+    lister.list_operands();
+    if let Some(records) = &mut lister.records {
+        for line in records.finish() {
+            write_line(line.as_bytes());
+        }
+    }
+    #[cfg(feature = "stat-count")]
+    if std::env::var_os("LS_COUNT_STATS").is_some() {
+        eprintln!("ls: {} stat calls", STAT_CALLS.load(Ordering::Relaxed));
+    }
+    process::exit(lister.exit_code);
 }
 
-fn list_directory(path: &Path, args: &args::LsArgs, depth: usize) -> anyhow::Result<()> {
-    let mut all_entries: Vec<PathBuf> = Vec::new();
+impl Lister<'_> {
+    /// List file operands first as one group, then the contents of each
+    /// directory operand
+    fn list_operands(&mut self) {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for operand in &self.args.paths {
+            let path = operand.clone();
+            let dereference = self.args.dereference || self.args.dereference_command_line;
+            let metadata = match stat(&path, dereference) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    self.report(
+                        EXIT_SERIOUS,
+                        format!(
+                            "cannot access '{}': {}",
+                            operand.display(),
+                            io_error_message(&e)
+                        ),
+                    );
+                    continue;
+                }
+            };
+            // Without -l, a symlink to a directory is listed as that directory
+            let is_dir = metadata.is_dir()
+                || (!self.args.long
+                    && metadata.file_type().is_symlink()
+                    && stat(&path, true).is_ok_and(|target| target.is_dir()));
+            let entry = Entry {
+                name: path.clone().into_os_string(),
+                path,
+                metadata: Some(metadata),
+            };
+            if is_dir {
+                dirs.push(entry);
+            } else {
+                files.push(entry);
+            }
+        }
+
+        self.sort(&mut files);
+        self.sort(&mut dirs);
 
-    // Collect all entries from the directory
-    for entry_result in fs::read_dir(path)? {
-        let entry_path = entry_result?.path();
-        all_entries.push(entry_path);
+        if !files.is_empty() {
+            self.print_entries(&files, false, None);
+            self.printed = true;
+        }
+        if self.args.tree {
+            // The summary only follows trees, not file operands on their own
+            let drawn = !dirs.is_empty();
+            let mut counts = Counts::default();
+            for dir in dirs {
+                self.list_tree(dir, &mut counts);
+            }
+            if drawn && self.records.is_none() {
+                write_line(b"");
+                write_line(counts.summary().as_bytes());
+            }
+            return;
+        }
+
+        let show_header = self.args.paths.len() > 1 || self.args.recursive;
+        for dir in &dirs {
+            self.root_device = stat(&dir.path, true).map_or(0, |metadata| metadata.dev());
+            self.list_directory(&dir.path, show_header, 0);
+        }
     }
 
-    let mut final_entries: Vec<PathBuf> = Vec::new();
+    /// List one directory, `depth` levels below the operand it was reached from
+    fn list_directory(&mut self, path: &Path, show_header: bool, depth: usize) {
+        let Some(stream) = self.open_directory(path) else {
+            return;
+        };
+        let Some(id) = self.enter_directory(path) else {
+            return;
+        };
 
-    if args.all {
-        final_entries.push(path.join("."));
-        final_entries.push(path.join(".."));
-        final_entries.extend(all_entries);
-    } else {
-        for entry_path in all_entries {
-            let file_name = entry_path
-                .file_name()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default();
-            if !file_name.starts_with('.') {
-                final_entries.push(entry_path);
+        // -U with one name per line prints each entry as soon as it is read,
+        // so output starts right away even for huge directories
+        let streaming = self.streams();
+        if streaming {
+            self.print_header(path, show_header);
+        }
+
+        let mut entries = self.read_directory(path, stream, streaming);
+        if !streaming {
+            if self.needs_metadata() {
+                self.stat_entries(&mut entries);
             }
+            self.sort(&mut entries);
+            self.print_header(path, show_header);
+            self.print_entries(&entries, true, None);
         }
-    }
 
-    // Custom sorting logic for . and .. and then by name, or by time/size
-    final_entries.sort_by(|a, b| {
-        let a_name = a
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
-        let b_name = b
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
-
-        // Prioritize "."
-        if a_name == "." && b_name != "." {
-            return Ordering::Less;
+        // Recursive listing
+        if self.args.recursive && self.args.max_depth.is_none_or(|max| depth < max) {
+            for entry in &entries {
+                if self.descends_into(entry) {
+                    self.list_directory(&entry.path, true, depth + 1);
+                }
+            }
         }
-        if a_name != "." && b_name == "." {
-            return Ordering::Greater;
+        self.active.remove(&id);
+    }
+
+    /// Whether recursion continues into an entry: a directory other than `.`
+    /// and `..` (a link to one only with -L), on the same file system as the
+    /// operand with --one-file-system
+    fn descends_into(&self, entry: &Entry) -> bool {
+        entry.name != "."
+            && entry.name != ".."
+            && entry.metadata.as_ref().is_some_and(|metadata| {
+                metadata.is_dir()
+                    && (!self.args.one_file_system || metadata.dev() == self.root_device)
+            })
+    }
+
+    /// Mark a directory as being listed, returning its device and inode.
+    /// A directory that is already being listed further up means a loop,
+    /// through symlinks with -L or through bind mounts, and is reported.
+    fn enter_directory(&mut self, path: &Path) -> Option<(u64, u64)> {
+        let id = match stat(path, true) {
+            Ok(metadata) => (metadata.dev(), metadata.ino()),
+            Err(e) => {
+                self.report(
+                    EXIT_SERIOUS,
+                    format!(
+                        "cannot access '{}': {}",
+                        path.display(),
+                        io_error_message(&e)
+                    ),
+                );
+                return None;
+            }
+        };
+        if !self.active.insert(id) {
+            self.report(
+                EXIT_SERIOUS,
+                format!("{}: not listing already-listed directory", path.display()),
+            );
+            return None;
         }
+        Some(id)
+    }
+
+    /// Print a directory operand and everything below it as a tree (--tree)
+    fn list_tree(&mut self, root: Entry, counts: &mut Counts) {
+        let path = root.path.clone();
+        self.root_device = stat(&path, true).map_or(0, |metadata| metadata.dev());
+        let mut entries = vec![root];
+        let mut branches = vec![Vec::new()];
+        self.walk_tree(&path, &[], 1, &mut entries, &mut branches, counts);
+        self.print_entries(&entries, false, Some(&branches));
+        self.printed = true;
+    }
 
-        // Prioritize ".." after "."
-        if a_name == ".." && b_name != ".." && b_name != "." {
-            return Ordering::Less;
+    /// Collect the sorted contents of `path` and, up to --level, of its
+    /// subdirectories, each with the branch drawn in front of its name
+    fn walk_tree(
+        &mut self,
+        path: &Path,
+        indent: &[u8],
+        depth: u64,
+        entries: &mut Vec<Entry>,
+        branches: &mut Vec<Vec<u8>>,
+        counts: &mut Counts,
+    ) {
+        let Some(stream) = self.open_directory(path) else {
+            return;
+        };
+        let Some(id) = self.enter_directory(path) else {
+            return;
+        };
+        let mut children = self.read_directory(path, stream, false);
+        children.retain(|entry| entry.name != "." && entry.name != "..");
+        self.stat_entries(&mut children);
+        self.sort(&mut children);
+
+        let charset = self.args.charset;
+        let count = children.len();
+        for (index, child) in children.into_iter().enumerate() {
+            let last = index + 1 == count;
+            // Like tree, a link to a directory counts as a directory, though
+            // it is only descended into with -L
+            let is_dir = sort::is_directory(&child);
+            let descends = self.descends_into(&child);
+            let child_path = child.path.clone();
+            branches.push([indent, charset.branch(last).as_bytes()].concat());
+            entries.push(child);
+
+            if !is_dir {
+                counts.files += 1;
+                continue;
+            }
+            counts.directories += 1;
+            if descends && self.args.level.is_none_or(|level| depth < level) {
+                let indent = [indent, charset.indent(last).as_bytes()].concat();
+                self.walk_tree(&child_path, &indent, depth + 1, entries, branches, counts);
+            }
         }
-        if a_name != ".." && b_name == ".." && a_name != "." {
-            return Ordering::Greater;
+        self.active.remove(&id);
+    }
+
+    /// Open a directory; one that cannot be opened is reported and skipped,
+    /// and makes the exit status 2
+    fn open_directory(&mut self, path: &Path) -> Option<DirStream> {
+        match DirStream::open(path) {
+            Ok(stream) => Some(stream),
+            Err(e) => {
+                self.report(
+                    EXIT_SERIOUS,
+                    format!(
+                        "cannot open directory '{}': {}",
+                        path.display(),
+                        io_error_message(&e)
+                    ),
+                );
+                None
+            }
         }
+    }
 
-        // Apply other sorting criteria
-        if args.sort_time {
-            let meta_a = fs::metadata(a).ok();
-            let meta_b = fs::metadata(b).ok();
-            match (meta_a, meta_b) {
-                (Some(ma), Some(mb)) => mb.modified().unwrap().cmp(&ma.modified().unwrap()), // Newest first
-                _ => a_name.cmp(b_name),
+    /// Read the entries of a directory that -a does not hide, in directory
+    /// order. When streaming, entries are printed as they are read instead of
+    /// returned.
+    fn read_directory(&mut self, path: &Path, stream: DirStream, streaming: bool) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for name in stream {
+            let name = match name {
+                Ok(name) => name,
+                Err(e) => {
+                    self.report(
+                        EXIT_MINOR,
+                        format!(
+                            "reading directory '{}': {}",
+                            path.display(),
+                            io_error_message(&e)
+                        ),
+                    );
+                    continue;
+                }
+            };
+            if self.args.all || !name.as_bytes().starts_with(b".") {
+                let entry = Entry {
+                    path: path.join(&name),
+                    name,
+                    metadata: None,
+                };
+                self.collect(entry, &mut entries, streaming);
             }
-        } else if args.sort_size {
-            let meta_a = fs::metadata(a).ok();
-            let meta_b = fs::metadata(b).ok();
-            match (meta_a, meta_b) {
-                (Some(ma), Some(mb)) => mb.len().cmp(&ma.len()), // Largest first
-                _ => a_name.cmp(b_name),
+        }
+        entries
+    }
+
+    /// Keep an entry for sorting, or print it right away when streaming. A
+    /// streamed entry is only kept when -R needs it afterwards.
+    fn collect(&mut self, mut entry: Entry, entries: &mut Vec<Entry>, streaming: bool) {
+        if !streaming {
+            entries.push(entry);
+            return;
+        }
+        if self.needs_metadata() {
+            let result = stat(&entry.path, self.args.dereference);
+            match self.inspect(&entry.path, result) {
+                Some(metadata) => entry.metadata = Some(metadata),
+                None => return,
             }
-        } else {
-            a_name.cmp(b_name) // Default alphabetical sort
         }
-    });
+        self.print_entries(std::slice::from_ref(&entry), false, None);
+        if self.args.recursive {
+            entries.push(entry);
+        }
+    }
 
-    if args.reverse {
-        final_entries.reverse();
+    /// Whether directory contents are printed while they are read: only
+    /// unsorted output with one name per line needs no full listing first
+    fn streams(&self) -> bool {
+        let args = self.args;
+        sort_key(args) == SortKey::None
+            && !args.long
+            && !args.size
+            && !args.group_directories_first
+            && name_layout(args).is_none()
     }
 
-    let mut total_blocks = 0;
-    let mut outputs = Vec::new();
+    /// Print the blank line between listings and the `dir:` header
+    fn print_header(&mut self, path: &Path, show_header: bool) {
+        if self.records.is_some() {
+            return;
+        }
+        if self.printed {
+            write_line(b"");
+        }
+        if show_header {
+            let mut header = self.quoting.quote_header(path.as_os_str().as_bytes());
+            header.push(b':');
+            write_line(&header);
+        }
+        self.printed = true;
+    }
 
-    let mut max_nlink_len = 0;
-    let mut max_user_len = 0;
-    let mut max_group_len = 0;
-    let mut max_size_len = 0;
+    /// Whether entries must be stat'ed; a plain listing of names needs none
+    fn needs_metadata(&self) -> bool {
+        let args = self.args;
+        args.long
+            || matches!(sort_key(args), SortKey::Size | SortKey::Time)
+            || args.group_directories_first
+            || args.size
+            || args.recursive
+            || args.tree
+            || args.format.is_some()
+            || self.colors.is_some()
+    }
 
-    if args.long {
-        for entry_path in &final_entries {
-            let metadata = match fs::metadata(entry_path) {
-                Ok(meta) => meta,
-                Err(_) => continue,
-            };
-            total_blocks += metadata.blocks();
+    fn sort(&self, entries: &mut Vec<Entry>) {
+        let args = self.args;
+        sort::sort_entries(
+            entries,
+            sort_key(args),
+            time_field(args),
+            args.reverse,
+            args.group_directories_first,
+        );
+    }
 
-            max_nlink_len = max_nlink_len.max(metadata.nlink().to_string().len());
-            max_user_len = max_user_len.max(
-                get_user_by_uid(metadata.uid())
-                    .map(|u| u.name().to_string_lossy().len())
-                    .unwrap_or_else(|| metadata.uid().to_string().len()),
-            );
-            max_group_len = max_group_len.max(
-                get_group_by_gid(metadata.gid())
-                    .map(|g| g.name().to_string_lossy().len())
-                    .unwrap_or_else(|| metadata.gid().to_string().len()),
-            );
-            max_size_len = max_size_len.max(if args.human_readable {
-                bytes_to_human_readable(metadata.len()).len()
-            } else {
-                metadata.len().to_string().len()
-            });
+    /// Fill in the metadata of every entry, dropping entries that vanished or
+    /// cannot be inspected
+    fn stat_entries(&mut self, entries: &mut Vec<Entry>) {
+        let results = stat_all(entries, self.args.dereference);
+        let mut kept = Vec::with_capacity(entries.len());
+        for (mut entry, result) in entries.drain(..).zip(results) {
+            if let Some(metadata) = self.inspect(&entry.path, result) {
+                entry.metadata = Some(metadata);
+                kept.push(entry);
+            }
         }
+        *entries = kept;
     }
 
-    // Print directory name for recursive listing
-    if args.recursive {
-        if depth == 0 {
-            println!("{}:", path.display());
-        } else {
-            println!("\n{}:", path.display());
+    /// Report a failed stat. With -L, a link that cannot be followed is still
+    /// listed as the link itself.
+    fn inspect(&mut self, path: &Path, result: io::Result<Metadata>) -> Option<Metadata> {
+        match result {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+                // Like GNU ls, entries of `.` are named without the `./`
+                let shown = match (path.parent(), path.file_name()) {
+                    (Some(parent), Some(name)) if parent == Path::new(".") => Path::new(name),
+                    _ => path,
+                };
+                self.report(
+                    EXIT_MINOR,
+                    format!(
+                        "cannot access '{}': {}",
+                        shown.display(),
+                        io_error_message(&e)
+                    ),
+                );
+                if self.args.dereference {
+                    stat(path, false).ok()
+                } else {
+                    None
+                }
+            }
         }
     }
 
-    if args.long {
-        if depth == 0 || args.recursive {
-            println!("total {}", total_blocks / 2); // Each block is 1024, but counted as 512, so divide by 2
+    /// Print an error and remember the exit status it calls for
+    fn report(&mut self, level: i32, message: impl Display) {
+        eprintln!("ls: {}", message);
+        self.exit_code = self.exit_code.max(level);
+    }
+
+    /// Print one --format record per entry
+    fn write_records(&mut self, entries: &[Entry]) {
+        for entry in entries {
+            let Some(metadata) = &entry.metadata else {
+                continue;
+            };
+            let user = self
+                .users
+                .get_user_by_uid(metadata.uid())
+                .map(|u| u.name().to_string_lossy().into_owned());
+            let group = self
+                .users
+                .get_group_by_gid(metadata.gid())
+                .map(|g| g.name().to_string_lossy().into_owned());
+            let record = Record::new(
+                &entry.path,
+                &entry.name,
+                metadata,
+                (user, group),
+                mode_string(metadata),
+            );
+            if let Some(records) = &mut self.records {
+                for line in records.lines(&record) {
+                    write_line(line.as_bytes());
+                }
+            }
         }
     }
 
-    for entry_path in &final_entries {
-        let metadata = match fs::metadata(entry_path) {
-            Ok(meta) => meta,
-            Err(_) => continue, // Skip files we can't get metadata for
+    /// Print a group of entries in the long or the name-only format. The
+    /// `total` line is only shown for directory contents. With `branches`,
+    /// entries are printed one per line as a tree, each name preceded by its
+    /// branch.
+    fn print_entries(&mut self, entries: &[Entry], show_total: bool, branches: Option<&[Vec<u8>]>) {
+        if self.records.is_some() {
+            self.write_records(entries);
+            return;
+        }
+
+        let args = self.args;
+        let colors = self.colors.as_ref();
+        let quoting = &self.quoting;
+        let layout = if branches.is_some() {
+            None
+        } else {
+            name_layout(args)
         };
 
-        let file_name = entry_path
-            .file_name()
-            .unwrap_or_default()
-            .to_str()
-            .unwrap_or_default();
+        let quoted: Vec<Vec<u8>> = entries
+            .iter()
+            .map(|entry| quoting.quote(entry.name.as_bytes()))
+            .collect();
+        // With the shell styles, unquoted names get a leading space to line up
+        // with quoted ones whenever any name in the group is quoted
+        let pad = quoting.aligns_outer_quotes()
+            && (args.long || layout.is_some_and(|(line_width, _)| line_width > 0))
+            && quoted
+                .iter()
+                .any(|name| matches!(name.first(), Some(b'\'' | b'"')));
+
+        // -s: allocated blocks of each entry, and their sum for `total`
+        let mut total_blocks = 0;
+        let blocks: Vec<String> = entries
+            .iter()
+            .map(|entry| match &entry.metadata {
+                Some(metadata) => {
+                    total_blocks += metadata.blocks();
+                    self.block_format.format(metadata.blocks(), BLOCK_UNIT)
+                }
+                None => String::new(),
+            })
+            .collect();
+        let max_blocks_len = blocks.iter().map(String::len).max().unwrap_or(0);
+
+        let mut outputs = Vec::new();
+        let mut cells = Vec::new();
+
+        // Owner and group names and the time of each entry, computed once for
+        // both passes
+        let mut owners = Vec::new();
+        let mut max_time_len = 0;
+        let now = time::now();
+        let mut max_nlink_len = 0;
+        let mut max_user_len = 0;
+        let mut max_group_len = 0;
+        let mut max_size_len = 0;
+        let mut max_major_len = 0;
+        let mut max_minor_len = 0;
 
         if args.long {
-            let file_type = if metadata.is_dir() {
-                'd'
-            } else if metadata.is_symlink() {
-                'l'
-            } else {
-                '-'
-            };
-
-            let perms = metadata.permissions();
-            let mode = perms.mode();
-            let rwx = |mode, r_mask, w_mask, x_mask| {
-                (
-                    if (mode & r_mask) != 0 { 'r' } else { '-' },
-                    if (mode & w_mask) != 0 { 'w' } else { '-' },
-                    if (mode & x_mask) != 0 { 'x' } else { '-' },
-                )
-            };
-
-            let (ur, uw, ux) = rwx(mode, 0o400, 0o200, 0o100);
-            let (gr, gw, gx) = rwx(mode, 0o040, 0o020, 0o010);
-            let (or, ow, ox) = rwx(mode, 0o004, 0o002, 0o001);
+            for metadata in entries.iter().filter_map(|entry| entry.metadata.as_ref()) {
+                let user = self
+                    .users
+                    .get_user_by_uid(metadata.uid())
+                    .map(|u| u.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| metadata.uid().to_string());
+                let group = self
+                    .users
+                    .get_group_by_gid(metadata.gid())
+                    .map(|g| g.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| metadata.gid().to_string());
+
+                max_nlink_len = max_nlink_len.max(metadata.nlink().to_string().len());
+                max_user_len = max_user_len.max(user.len());
+                max_group_len = max_group_len.max(group.len());
+                let time = self.time_style.format(time_field(args).of(metadata), now);
+                max_time_len = max_time_len.max(time.chars().count());
+                owners.push((user, group, time));
+                if is_device(metadata) {
+                    let rdev = metadata.rdev();
+                    max_major_len = max_major_len.max(libc::major(rdev).to_string().len());
+                    max_minor_len = max_minor_len.max(libc::minor(rdev).to_string().len());
+                } else {
+                    max_size_len =
+                        max_size_len.max(self.size_format.format(metadata.len(), 1).len());
+                }
+            }
+            if max_major_len > 0 {
+                max_size_len = max_size_len.max(max_major_len + 2 + max_minor_len);
+            }
+        }
 
-            let nlink = metadata.nlink().to_string();
+        if (args.long || args.size) && show_total {
+            let total = self.block_format.format(total_blocks, BLOCK_UNIT);
+            write_line(format!("total {}", total).as_bytes());
+        }
 
-            let user = get_user_by_uid(metadata.uid())
-                .map(|u| u.name().to_string_lossy().into_owned())
-                .unwrap_or_else(|| metadata.uid().to_string());
+        let mut owners = owners.into_iter();
+        for (index, ((entry, quoted), blocks)) in entries.iter().zip(quoted).zip(blocks).enumerate()
+        {
+            let mut display_name = Vec::new();
+            if let Some(branches) = branches {
+                display_name.extend_from_slice(&branches[index]);
+            }
+            if pad && !matches!(quoted.first(), Some(b'\'' | b'"')) {
+                display_name.push(b' ');
+            }
+            let width = quote::display_width(&display_name) + quote::display_width(&quoted);
+            match (colors, &entry.metadata) {
+                (Some(colors), Some(metadata)) => {
+                    display_name.extend(colors.paint(&entry.path, metadata, &entry.name, &quoted))
+                }
+                _ => display_name.extend(quoted),
+            }
 
-            let group = get_group_by_gid(metadata.gid())
-                .map(|g| g.name().to_string_lossy().into_owned())
-                .unwrap_or_else(|| metadata.gid().to_string());
+            if args.long
+                && let Some(metadata) = &entry.metadata
+                && let Some((user, group, time)) = owners.next()
+            {
+                let nlink = metadata.nlink().to_string();
+
+                let size = if is_device(metadata) {
+                    let rdev = metadata.rdev();
+                    format!(
+                        "{:>width_major$}, {:>width_minor$}",
+                        libc::major(rdev),
+                        libc::minor(rdev),
+                        width_major = max_major_len,
+                        width_minor = max_minor_len,
+                    )
+                } else {
+                    self.size_format.format(metadata.len(), 1)
+                };
 
-            let size = if args.human_readable {
-                bytes_to_human_readable(metadata.len())
-            } else {
-                metadata.len().to_string()
-            };
+                let time_padding = if self.time_style.pads() {
+                    max_time_len - time.chars().count()
+                } else {
+                    0
+                };
 
-            let modified: DateTime<Local> = DateTime::from(metadata.modified()?);
-            let time_format =
-                if modified.format("%Y").to_string() == Local::now().format("%Y").to_string() {
-                    modified.format("%b %e %H:%M").to_string()
+                let mut line = if args.size {
+                    format!("{:>max_blocks_len$} ", blocks).into_bytes()
                 } else {
-                    modified.format("%b %e  %Y").to_string()
+                    Vec::new()
                 };
+                line.extend(format!(
+                    "{} {:>width_nlink$} {:<width_user$} {:<width_group$} {:>width_size$} {}{:time_padding$} ",
+                    mode_string(metadata),
+                    nlink,
+                    user,
+                    group,
+                    size,
+                    time,
+                    "",
+                    width_nlink = max_nlink_len,
+                    width_user = max_user_len,
+                    width_group = max_group_len,
+                    width_size = max_size_len,
+                ).into_bytes());
+                line.extend(display_name);
+                if metadata.file_type().is_symlink()
+                    && let Ok(target) = fs::read_link(&entry.path)
+                {
+                    line.extend_from_slice(b" -> ");
+                    line.extend(link_target_name(&entry.path, &target, colors, quoting));
+                }
+                outputs.push(line);
+            } else if args.size {
+                let mut text = format!("{:>max_blocks_len$} ", blocks).into_bytes();
+                text.extend(display_name);
+                cells.push(Cell::new(text, max_blocks_len + 1 + width));
+            } else {
+                cells.push(Cell::new(display_name, width));
+            }
+        }
 
-            let perms_str = format!("{}{}{}{}{}{}{}{}{}", ur, uw, ux, gr, gw, gx, or, ow, ox);
-
-            outputs.push(format!(
-                "{}{} {:>width_nlink$} {:<width_user$} {:<width_group$} {:>width_size$} {} {}",
-                file_type,
-                perms_str,
-                nlink,
-                user,
-                group,
-                size,
-                time_format,
-                file_name,
-                width_nlink = max_nlink_len,
-                width_user = max_user_len,
-                width_group = max_group_len,
-                width_size = max_size_len,
-            ));
+        if let Some((line_width, direction)) = layout {
+            outputs.extend(grid::layout(&cells, line_width, direction));
         } else {
-            outputs.push(file_name.to_string());
+            outputs.extend(cells.into_iter().map(|cell| cell.text));
+        }
+
+        for output in outputs {
+            write_line(&output);
         }
     }
+}
 
-    for output in outputs {
-        println!("{}", output);
+/// `stat` a path, following a symlink only when `dereference` is set. Every
+/// stat made by ls goes through here.
+fn stat(path: &Path, dereference: bool) -> io::Result<Metadata> {
+    #[cfg(feature = "stat-count")]
+    STAT_CALLS.fetch_add(1, Ordering::Relaxed);
+    if dereference {
+        fs::metadata(path)
+    } else {
+        fs::symlink_metadata(path)
     }
+}
 
-    // Recursive listing
-    if args.recursive {
-        for entry_path in &final_entries {
-            if entry_path.is_dir()
-                && !entry_path
-                    .file_name()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or_default()
-                    .starts_with('.')
-            {
-                list_directory(entry_path, args, depth + 1)?;
-            }
-        }
+/// `lstat` (or with `dereference`, `stat`) every entry, spreading large directories over a few threads so
+/// that slow file systems such as NFS are queried concurrently
+fn stat_all(entries: &[Entry], dereference: bool) -> Vec<io::Result<Metadata>> {
+    let stat_chunk = |chunk: &[Entry]| {
+        chunk
+            .iter()
+            .map(|entry| stat(&entry.path, dereference))
+            .collect::<Vec<_>>()
+    };
+    if entries.len() < PARALLEL_STAT_THRESHOLD {
+        return stat_chunk(entries);
+    }
+    let chunk_size = entries.len().div_ceil(STAT_THREADS);
+    thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(move || stat_chunk(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Render the file type character and the nine permission characters,
+/// including setuid (`s`/`S`), setgid (`s`/`S`) and sticky (`t`/`T`) bits
+fn mode_string(metadata: &fs::Metadata) -> String {
+    let file_type = metadata.file_type();
+    let type_char = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+
+    let mode = metadata.mode();
+    let bit = |mask: u32, c: char| if mode & mask != 0 { c } else { '-' };
+    // Execute position, which doubles as the place for a special bit
+    let exec = |x_mask: u32, special_mask: u32, special: char| match (
+        mode & x_mask != 0,
+        mode & special_mask != 0,
+    ) {
+        (true, true) => special,
+        (false, true) => special.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+
+    [
+        type_char,
+        bit(0o400, 'r'),
+        bit(0o200, 'w'),
+        exec(0o100, 0o4000, 's'),
+        bit(0o040, 'r'),
+        bit(0o020, 'w'),
+        exec(0o010, 0o2000, 's'),
+        bit(0o004, 'r'),
+        bit(0o002, 'w'),
+        exec(0o001, 0o1000, 't'),
+    ]
+    .iter()
+    .collect()
+}
+
+fn is_device(metadata: &fs::Metadata) -> bool {
+    let file_type = metadata.file_type();
+    file_type.is_block_device() || file_type.is_char_device()
+}
+
+/// The `target` part of `name -> target`, colored like the file it points to,
+/// or as missing when the link is broken
+fn link_target_name(
+    link: &Path,
+    target: &Path,
+    colors: Option<&Colors>,
+    quoting: &Quoting,
+) -> Vec<u8> {
+    let quoted = quoting.quote(target.as_os_str().as_bytes());
+    let Some(colors) = colors else {
+        return quoted;
+    };
+    let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+    colors.paint_target(&resolved, target.as_os_str(), &quoted)
+}
+
+/// The sort key picked by the last of -t, -S, -X, -v, -U and --sort
+fn sort_key(args: &LsArgs) -> SortKey {
+    if args.sort_time {
+        SortKey::Time
+    } else if args.sort_size {
+        SortKey::Size
+    } else if args.sort_extension {
+        SortKey::Extension
+    } else if args.sort_version {
+        SortKey::Version
+    } else if args.unsorted {
+        SortKey::None
+    } else {
+        // Like GNU ls, -u and -c sort by their time unless -l shows it
+        args.sort.unwrap_or(
+            if !args.long && time_field(args) != TimeField::Modification {
+                SortKey::Time
+            } else {
+                SortKey::Name
+            },
+        )
+    }
+}
+
+/// How -l shows sizes: -H, --si or --block-size, else the LS_BLOCK_SIZE or
+/// BLOCK_SIZE environment variable, else bytes
+fn size_format(args: &LsArgs) -> SizeFormat {
+    explicit_size_format(args)
+        .or_else(environment_size_format)
+        .unwrap_or_else(SizeFormat::bytes)
+}
+
+/// How -s and `total` show blocks: like sizes, except that -k or nothing at
+/// all means 1024-byte blocks
+fn block_format(args: &LsArgs) -> SizeFormat {
+    explicit_size_format(args)
+        .or_else(|| (!args.kibibytes).then(environment_size_format).flatten())
+        .unwrap_or_else(SizeFormat::kibibytes)
+}
+
+fn explicit_size_format(args: &LsArgs) -> Option<SizeFormat> {
+    if args.human_readable {
+        Some(SizeFormat::human(1024))
+    } else if args.si {
+        Some(SizeFormat::human(1000))
+    } else {
+        args.block_size.clone()
     }
+}
+
+fn environment_size_format() -> Option<SizeFormat> {
+    ["LS_BLOCK_SIZE", "BLOCK_SIZE"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok())
+        .and_then(|spec| SizeFormat::parse(&spec).ok())
+}
+
+/// The timestamp picked by the last of -u, -c and --time
+fn time_field(args: &LsArgs) -> TimeField {
+    if args.access_time {
+        TimeField::Access
+    } else if args.change_time {
+        TimeField::Change
+    } else {
+        args.time.unwrap_or(TimeField::Modification)
+    }
+}
+
+/// The --time-style for -l: --full-time, then the option, then the
+/// TIME_STYLE environment variable, else the locale style
+fn time_style(args: &LsArgs) -> TimeStyle {
+    if args.full_time {
+        return TimeStyle::FullIso;
+    }
+    args.time_style
+        .clone()
+        .or_else(|| {
+            std::env::var("TIME_STYLE")
+                .ok()
+                .and_then(|style| TimeStyle::parse(&style).ok())
+        })
+        .unwrap_or(TimeStyle::Locale)
+}
 
-    Ok(())
+/// Decide how names are arranged outside the long format: `None` prints one
+/// name per line, otherwise names are packed into columns of the given width.
+fn name_layout(args: &LsArgs) -> Option<(usize, Direction)> {
+    if args.long || args.one_per_line {
+        return None;
+    }
+    let terminal_width = grid::terminal_width();
+    let direction = if args.across {
+        Direction::Across
+    } else if args.columns || terminal_width.is_some() {
+        Direction::Down
+    } else {
+        // Piped output defaults to one name per line
+        return None;
+    };
+
+    let line_width = args
+        .width
+        .or_else(|| {
+            std::env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.parse().ok())
+                .filter(|&columns| columns > 0)
+        })
+        .or(terminal_width)
+        .unwrap_or(80);
+    Some((line_width, direction))
 }
 
-fn bytes_to_human_readable(bytes: u64) -> String {
-    if bytes < 1024 {
-        return bytes.to_string();
+/// Pick the quoting style: the last of --quoting-style, -b and -N, then the
+/// QUOTING_STYLE variable, then shell-escape on a terminal and literal otherwise.
+/// Non-printable characters are shown as `?` on a terminal unless
+/// --show-control-chars is given.
+fn name_quoting(args: &LsArgs) -> Quoting {
+    let terminal = io::stdout().is_terminal();
+    let style = if args.escape {
+        QuotingStyle::Escape
+    } else if args.literal {
+        QuotingStyle::Literal
+    } else if let Some(style) = args.quoting_style {
+        style
+    } else if let Some(style) = std::env::var("QUOTING_STYLE")
+        .ok()
+        .and_then(|style| QuotingStyle::from_str(&style, false).ok())
+    {
+        style
+    } else if terminal {
+        QuotingStyle::ShellEscape
+    } else {
+        QuotingStyle::Literal
+    };
+    let hide_control = if args.hide_control_chars {
+        true
+    } else if args.show_control_chars {
+        false
+    } else {
+        terminal
+    };
+    Quoting::new(style, hide_control)
+}
+
+/// Write one line of output; a failed write ends the program like GNU ls does
+fn write_line(line: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    if let Err(e) = stdout
+        .write_all(line)
+        .and_then(|()| stdout.write_all(b"\n"))
+    {
+        eprintln!("ls: write error: {}", io_error_message(&e));
+        process::exit(EXIT_SERIOUS);
     }
-    let units = ["K", "M", "G", "T", "P", "E", "Z", "Y"];
-    let mut display_bytes = bytes as f64;
-    let mut unit_index = 0;
-    while display_bytes >= 1024.0 && unit_index < units.len() - 1 {
-        display_bytes /= 1024.0;
-        unit_index += 1;
+}
+
+/// Format an I/O error without the " (os error N)" suffix, as GNU ls does
+fn io_error_message(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
     }
-    format!("{:.1}{}", display_bytes, units[unit_index])
 }
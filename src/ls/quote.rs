@@ -0,0 +1,302 @@
+use clap::ValueEnum;
+use unicode_width::UnicodeWidthChar;
+
+/// How file names are quoted on output (--quoting-style), following GNU ls
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum QuotingStyle {
+    /// Print names as they are
+    Literal,
+    /// Quote names for the shell when they need it
+    Shell,
+    /// Always quote names for the shell
+    ShellAlways,
+    /// Like `shell`, but write non-printable characters as `$'\n'` escapes
+    ShellEscape,
+    /// Like `shell-always`, but write non-printable characters as `$'\n'` escapes
+    ShellEscapeAlways,
+    /// Quote as a C string
+    C,
+    /// Backslash escapes as in C, without surrounding quotes
+    Escape,
+    /// Like `escape`, surrounded by the quotation marks of the locale
+    Locale,
+}
+
+/// Quoting settings shared by every name printed in one run
+pub struct Quoting {
+    style: QuotingStyle,
+    /// -q: show non-printable characters as `?`
+    hide_control: bool,
+    /// Whether the locale uses UTF-8, which decides which characters are
+    /// printable and which quotation marks `locale` uses
+    utf8: bool,
+}
+
+/// One piece of a name: a printable character kept as is, or a byte that
+/// needs an escape in the backslash styles
+enum Unit<'a> {
+    Printable(&'a [u8]),
+    Unprintable(u8),
+}
+
+impl Quoting {
+    pub fn new(style: QuotingStyle, hide_control: bool) -> Self {
+        let utf8 = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|key| std::env::var(key).ok().filter(|value| !value.is_empty()))
+            .is_some_and(|locale| {
+                let locale = locale.to_ascii_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            });
+        Quoting {
+            style,
+            hide_control,
+            utf8,
+        }
+    }
+
+    /// Whether names that need no quotes are padded with a space so they line
+    /// up with quoted ones, as GNU ls does for the shell styles
+    pub fn aligns_outer_quotes(&self) -> bool {
+        matches!(self.style, QuotingStyle::Shell | QuotingStyle::ShellEscape)
+    }
+
+    /// Quote a file name; the `escape` style also escapes spaces in names
+    pub fn quote(&self, name: &[u8]) -> Vec<u8> {
+        let special: &[u8] = if self.style == QuotingStyle::Escape {
+            b" "
+        } else {
+            b""
+        };
+        self.quote_with(name, special)
+    }
+
+    /// Quote a directory name for a `dir:` header, where `:` must be escaped
+    pub fn quote_header(&self, name: &[u8]) -> Vec<u8> {
+        self.quote_with(name, b":")
+    }
+
+    fn quote_with(&self, name: &[u8], special: &[u8]) -> Vec<u8> {
+        let quoted = match self.style {
+            QuotingStyle::Literal => name.to_vec(),
+            QuotingStyle::Shell => self.shell(name, false, true, special),
+            QuotingStyle::ShellAlways => self.shell(name, false, false, special),
+            QuotingStyle::ShellEscape => self.shell(name, true, true, special),
+            QuotingStyle::ShellEscapeAlways => self.shell(name, true, false, special),
+            QuotingStyle::C => self.backslash(name, "\"", "\"", special),
+            QuotingStyle::Escape => self.backslash(name, "", "", special),
+            QuotingStyle::Locale if self.utf8 => {
+                self.backslash(name, "\u{2018}", "\u{2019}", special)
+            }
+            QuotingStyle::Locale => self.backslash(name, "'", "'", special),
+        };
+        if self.hide_control {
+            self.hide_unprintable(&quoted)
+        } else {
+            quoted
+        }
+    }
+
+    /// Split a name into printable characters and unprintable bytes for the
+    /// current locale; invalid UTF-8 is always unprintable
+    fn units<'a>(&self, name: &'a [u8]) -> Vec<Unit<'a>> {
+        let mut units = Vec::new();
+        let mut rest = name;
+        while let Some(&byte) = rest.first() {
+            let len = if byte.is_ascii() {
+                1
+            } else if self.utf8 {
+                utf8_char_len(rest)
+            } else {
+                0
+            };
+            let printable = match len {
+                0 => false,
+                1 => (0x20..0x7f).contains(&byte),
+                _ => std::str::from_utf8(&rest[..len])
+                    .is_ok_and(|c| c.chars().all(|c| !c.is_control())),
+            };
+            if printable {
+                units.push(Unit::Printable(&rest[..len]));
+                rest = &rest[len..];
+            } else {
+                units.push(Unit::Unprintable(byte));
+                rest = &rest[1..];
+            }
+        }
+        units
+    }
+
+    /// The `c`, `escape` and `locale` styles: C backslash escapes between `left`
+    /// and `right`, with octal escapes for unprintable bytes
+    fn backslash(&self, name: &[u8], left: &str, right: &str, special: &[u8]) -> Vec<u8> {
+        let mut out = left.as_bytes().to_vec();
+        for unit in self.units(name) {
+            match unit {
+                Unit::Printable(bytes) if bytes == b"\\" => out.extend_from_slice(b"\\\\"),
+                Unit::Printable(bytes)
+                    if (!right.is_empty() && right.as_bytes() == bytes)
+                        || (bytes.len() == 1 && special.contains(&bytes[0])) =>
+                {
+                    out.push(b'\\');
+                    out.extend_from_slice(bytes);
+                }
+                Unit::Printable(bytes) => out.extend_from_slice(bytes),
+                Unit::Unprintable(byte) => push_escape(&mut out, byte),
+            }
+        }
+        out.extend_from_slice(right.as_bytes());
+        out
+    }
+
+    /// The shell styles. With `escapes`, unprintable bytes are written as
+    /// `$'...'` segments; with `elide`, quotes are only added when needed.
+    fn shell(&self, name: &[u8], escapes: bool, elide: bool, special: &[u8]) -> Vec<u8> {
+        let units = self.units(name);
+        if elide && !self.needs_shell_quotes(name, &units, escapes, special) {
+            return name.to_vec();
+        }
+
+        // A name with single quotes that is otherwise harmless reads better in
+        // double quotes, e.g. "it's"
+        if name.contains(&b'\'')
+            && units.iter().enumerate().all(|(index, unit)| match unit {
+                Unit::Printable(bytes) => shell_quote_compatible(bytes, index),
+                Unit::Unprintable(_) => false,
+            })
+        {
+            return self.backslash(name, "\"", "\"", special);
+        }
+
+        let mut out = vec![b'\''];
+        let mut in_escape = false;
+        for unit in units {
+            match unit {
+                Unit::Unprintable(byte) if escapes => {
+                    if !in_escape {
+                        out.extend_from_slice(b"'$'");
+                        in_escape = true;
+                    }
+                    push_escape(&mut out, byte);
+                }
+                Unit::Printable(b"'") => {
+                    out.extend_from_slice(b"'\\''");
+                    in_escape = false;
+                }
+                unit => {
+                    if in_escape {
+                        out.extend_from_slice(b"''");
+                        in_escape = false;
+                    }
+                    match unit {
+                        Unit::Printable(bytes) => out.extend_from_slice(bytes),
+                        Unit::Unprintable(byte) => out.push(byte),
+                    }
+                }
+            }
+        }
+        out.push(b'\'');
+        out
+    }
+
+    fn needs_shell_quotes(
+        &self,
+        name: &[u8],
+        units: &[Unit],
+        escapes: bool,
+        special: &[u8],
+    ) -> bool {
+        name.is_empty()
+            || units.iter().enumerate().any(|(index, unit)| match *unit {
+                Unit::Printable(bytes) if bytes.len() == 1 => {
+                    let byte = bytes[0];
+                    special.contains(&byte)
+                        || b" !\"$&()*;<=>?[\\^`|'".contains(&byte)
+                        || (index == 0 && (byte == b'#' || byte == b'~'))
+                        || (name.len() == 1 && (byte == b'{' || byte == b'}'))
+                }
+                Unit::Printable(_) => false,
+                Unit::Unprintable(byte) => escapes || escape_letter(byte).is_some(),
+            })
+    }
+
+    /// -q: replace every unprintable character of the quoted output with `?`
+    fn hide_unprintable(&self, quoted: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(quoted.len());
+        for unit in self.units(quoted) {
+            match unit {
+                Unit::Printable(bytes) => out.extend_from_slice(bytes),
+                Unit::Unprintable(_) => out.push(b'?'),
+            }
+        }
+        out
+    }
+}
+
+/// Display width of quoted output, counted like GNU ls does in a UTF-8
+/// locale: control characters take no columns and each byte that is not valid
+/// UTF-8 takes one
+pub fn display_width(text: &[u8]) -> usize {
+    text.utf8_chunks()
+        .map(|chunk| {
+            let valid: usize = chunk
+                .valid()
+                .chars()
+                .filter(|c| !c.is_control())
+                .map(|c| UnicodeWidthChar::width(c).unwrap_or(0))
+                .sum();
+            valid + chunk.invalid().len()
+        })
+        .sum()
+}
+
+/// Whether a character may appear in a name shown in double quotes instead of
+/// the shell's single quotes
+fn shell_quote_compatible(bytes: &[u8], index: usize) -> bool {
+    match bytes {
+        [byte] => match byte {
+            b' ' | b'\'' => true,
+            b'#' | b'~' => index == 0,
+            b'!' | b'"' | b'$' | b'&' | b'(' | b')' | b'*' | b';' | b'<' | b'=' | b'>' | b'['
+            | b'^' | b'`' | b'|' | b'?' | b'\\' | b'{' | b'}' => false,
+            _ => true,
+        },
+        _ => true,
+    }
+}
+
+fn escape_letter(byte: u8) -> Option<u8> {
+    match byte {
+        0x07 => Some(b'a'),
+        0x08 => Some(b'b'),
+        0x0c => Some(b'f'),
+        b'\n' => Some(b'n'),
+        b'\r' => Some(b'r'),
+        b'\t' => Some(b't'),
+        0x0b => Some(b'v'),
+        _ => None,
+    }
+}
+
+/// Append `\n`-style escapes for control characters and `\ooo` for other bytes
+fn push_escape(out: &mut Vec<u8>, byte: u8) {
+    out.push(b'\\');
+    match escape_letter(byte) {
+        Some(letter) => out.push(letter),
+        None => out.extend_from_slice(format!("{:03o}", byte).as_bytes()),
+    }
+}
+
+/// Length of the UTF-8 sequence at the start of `bytes`, or 0 when it is invalid
+fn utf8_char_len(bytes: &[u8]) -> usize {
+    let len = match bytes[0] {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return 0,
+    };
+    match bytes.get(..len) {
+        Some(sequence) if std::str::from_utf8(sequence).is_ok() => len,
+        _ => 0,
+    }
+}
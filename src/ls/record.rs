@@ -0,0 +1,246 @@
+use crate::time::TimeField;
+use base64::prelude::{BASE64_STANDARD, Engine};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs::{self, Metadata};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::Path;
+
+/// Machine-readable output formats (--format)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum OutputFormat {
+    /// One JSON array holding every entry
+    Json,
+    /// One JSON object per line
+    Ndjson,
+    /// Comma-separated values with a header line
+    Csv,
+}
+
+/// Columns of the CSV output, in the order of the fields of `Record`
+const CSV_HEADER: &str = "name,path,encoding,type,mode,mode_symbolic,nlink,uid,user,gid,group,\
+                          size,blocks,inode,device,rdev,atime,mtime,ctime,btime,target";
+
+/// A timestamp with nanoseconds
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct Time {
+    pub sec: i64,
+    pub nsec: i64,
+}
+
+/// Everything known about one entry. Names, paths and link targets are
+/// UTF-8 text when they all can be; otherwise all three are base64 of the raw
+/// bytes and `encoding` says so.
+#[derive(Serialize, Debug)]
+pub struct Record {
+    pub name: String,
+    pub path: String,
+    pub encoding: &'static str,
+    #[serde(rename = "type")]
+    pub file_type: &'static str,
+    /// Permission and special bits in octal, e.g. `0755`
+    pub mode: String,
+    /// The mode as shown by -l, e.g. `drwxr-xr-x`
+    pub mode_symbolic: String,
+    pub nlink: u64,
+    pub uid: u32,
+    pub user: Option<String>,
+    pub gid: u32,
+    pub group: Option<String>,
+    pub size: u64,
+    /// Allocated 512-byte blocks
+    pub blocks: u64,
+    pub inode: u64,
+    pub device: u64,
+    /// The device a block or character special file stands for
+    pub rdev: u64,
+    pub atime: Time,
+    pub mtime: Time,
+    pub ctime: Time,
+    /// Creation time, where the file system records it
+    pub btime: Option<Time>,
+    pub target: Option<String>,
+}
+
+impl Record {
+    pub fn new(
+        path: &Path,
+        name: &OsStr,
+        metadata: &Metadata,
+        owners: (Option<String>, Option<String>),
+        mode_symbolic: String,
+    ) -> Self {
+        // Only symlinks have a target, so other entries cost no extra syscall
+        let target = if metadata.file_type().is_symlink() {
+            fs::read_link(path).ok()
+        } else {
+            None
+        };
+        let texts = [
+            name.as_bytes(),
+            path.as_os_str().as_bytes(),
+            target
+                .as_ref()
+                .map_or(&[][..], |target| target.as_os_str().as_bytes()),
+        ];
+        let utf8 = texts.iter().all(|text| std::str::from_utf8(text).is_ok());
+        let encode = |text: &[u8]| {
+            if utf8 {
+                String::from_utf8_lossy(text).into_owned()
+            } else {
+                BASE64_STANDARD.encode(text)
+            }
+        };
+        let time = |(sec, nsec)| Time { sec, nsec };
+
+        Record {
+            name: encode(texts[0]),
+            path: encode(texts[1]),
+            encoding: if utf8 { "utf-8" } else { "base64" },
+            file_type: type_name(metadata),
+            mode: format!("{:04o}", metadata.mode() & 0o7777),
+            mode_symbolic,
+            nlink: metadata.nlink(),
+            uid: metadata.uid(),
+            user: owners.0,
+            gid: metadata.gid(),
+            group: owners.1,
+            size: metadata.len(),
+            blocks: metadata.blocks(),
+            inode: metadata.ino(),
+            device: metadata.dev(),
+            rdev: metadata.rdev(),
+            atime: time((metadata.atime(), metadata.atime_nsec())),
+            mtime: time((metadata.mtime(), metadata.mtime_nsec())),
+            ctime: time((metadata.ctime(), metadata.ctime_nsec())),
+            btime: TimeField::Birth.of(metadata).map(time),
+            target: target.as_ref().map(|_| encode(texts[2])),
+        }
+    }
+
+    fn csv_fields(&self) -> Vec<String> {
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        vec![
+            self.name.clone(),
+            self.path.clone(),
+            self.encoding.to_string(),
+            self.file_type.to_string(),
+            self.mode.clone(),
+            self.mode_symbolic.clone(),
+            self.nlink.to_string(),
+            self.uid.to_string(),
+            optional(&self.user),
+            self.gid.to_string(),
+            optional(&self.group),
+            self.size.to_string(),
+            self.blocks.to_string(),
+            self.inode.to_string(),
+            self.device.to_string(),
+            self.rdev.to_string(),
+            csv_time(self.atime),
+            csv_time(self.mtime),
+            csv_time(self.ctime),
+            self.btime.map(csv_time).unwrap_or_default(),
+            optional(&self.target),
+        ]
+    }
+}
+
+/// Turns records into output lines, adding what the format needs around them
+pub struct RecordWriter {
+    format: OutputFormat,
+    written: usize,
+    /// The last JSON object, held back until it is known whether a comma
+    /// must follow it
+    pending: Option<String>,
+}
+
+impl RecordWriter {
+    pub fn new(format: OutputFormat) -> Self {
+        RecordWriter {
+            format,
+            written: 0,
+            pending: None,
+        }
+    }
+
+    /// The lines to print for the next record
+    pub fn lines(&mut self, record: &Record) -> Vec<String> {
+        let mut lines = Vec::new();
+        let first = self.written == 0;
+        self.written += 1;
+        match self.format {
+            OutputFormat::Json => {
+                match self.pending.take() {
+                    Some(previous) => lines.push(format!("  {},", previous)),
+                    None => lines.push("[".to_string()),
+                }
+                self.pending = Some(serde_json::to_string(record).unwrap());
+            }
+            OutputFormat::Ndjson => lines.push(serde_json::to_string(record).unwrap()),
+            OutputFormat::Csv => {
+                if first {
+                    lines.push(CSV_HEADER.to_string());
+                }
+                let fields: Vec<String> = record
+                    .csv_fields()
+                    .iter()
+                    .map(|field| csv_field(field))
+                    .collect();
+                lines.push(fields.join(","));
+            }
+        }
+        lines
+    }
+
+    /// The lines left to print once every record has been written
+    pub fn finish(&mut self) -> Vec<String> {
+        match self.format {
+            OutputFormat::Json => match self.pending.take() {
+                Some(last) => vec![format!("  {}", last), "]".to_string()],
+                None => vec!["[]".to_string()],
+            },
+            OutputFormat::Csv if self.written == 0 => vec![CSV_HEADER.to_string()],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block-device"
+    } else if file_type.is_char_device() {
+        "char-device"
+    } else {
+        "file"
+    }
+}
+
+/// `sec.nnnnnnnnn`, also for times before the epoch
+fn csv_time(time: Time) -> String {
+    if time.sec < 0 && time.nsec > 0 {
+        format!("-{}.{:09}", -(time.sec + 1), 1_000_000_000 - time.nsec)
+    } else {
+        format!("{}.{:09}", time.sec, time.nsec)
+    }
+}
+
+/// Quote a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
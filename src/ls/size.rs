@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+use std::ffi::CStr;
+
+/// Suffixes for each power of the base when sizes are scaled
+const POWERS_1000: &[u8] = b"kMGTPEZYRQ";
+const POWERS_1024: &[u8] = b"KMGTPEZYRQ";
+
+/// How sizes and block counts are printed (-H, --si, --block-size, -k).
+/// Numbers are always rounded up, as in GNU ls.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SizeFormat {
+    /// Unit that amounts are divided by
+    block_size: u64,
+    /// Base to scale by for human-readable output, 1000 or 1024
+    autoscale: Option<u64>,
+    /// Printed after every number, e.g. `K` for --block-size=K
+    suffix: String,
+    /// Thousands separator of the locale, when `'` asked for grouping
+    separator: String,
+}
+
+impl SizeFormat {
+    /// Plain byte counts
+    pub fn bytes() -> Self {
+        Self::units(1)
+    }
+
+    /// Counts of 1024-byte blocks, the default for block counts (-k)
+    pub fn kibibytes() -> Self {
+        Self::units(1024)
+    }
+
+    /// Scale to the largest fitting power of `base`, e.g. `1.1K` (-H, --si)
+    pub fn human(base: u64) -> Self {
+        SizeFormat {
+            autoscale: Some(base),
+            ..Self::units(1)
+        }
+    }
+
+    fn units(block_size: u64) -> Self {
+        SizeFormat {
+            block_size,
+            autoscale: None,
+            suffix: String::new(),
+            separator: String::new(),
+        }
+    }
+
+    /// Parse a --block-size argument such as `K`, `1M`, `KB`, `KiB`,
+    /// `human-readable` or `si`. A leading `'` groups thousands; a unit given
+    /// without a number is printed after every size.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid block size '{}'", spec);
+        let (group, rest) = match spec.strip_prefix('\'') {
+            Some(rest) => (true, rest),
+            None => (false, spec),
+        };
+        let mut format = match rest {
+            "human-readable" => Self::human(1024),
+            "si" => Self::human(1000),
+            _ => {
+                let digits_end = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                let (digits, unit) = rest.split_at(digits_end);
+                let count: u64 = match digits {
+                    "" => 1,
+                    digits => digits.parse().map_err(|_| invalid())?,
+                };
+                let (multiplier, suffix) = match unit.chars().next() {
+                    None => (1, String::new()),
+                    Some(letter) => {
+                        let power = POWERS_1024
+                            .iter()
+                            .position(|&p| p == letter.to_ascii_uppercase() as u8)
+                            .ok_or_else(invalid)?;
+                        let (base, tail, letters) = match &unit[1..] {
+                            "" => (1024, "", POWERS_1024),
+                            "iB" => (1024, "iB", POWERS_1024),
+                            "B" => (1000, "B", POWERS_1000),
+                            _ => return Err(invalid()),
+                        };
+                        let multiplier = (0..=power)
+                            .try_fold(1u64, |total, _| total.checked_mul(base))
+                            .ok_or_else(invalid)?;
+                        (multiplier, format!("{}{}", letters[power] as char, tail))
+                    }
+                };
+                let block_size = count
+                    .checked_mul(multiplier)
+                    .filter(|&size| size > 0)
+                    .ok_or_else(invalid)?;
+                SizeFormat {
+                    suffix: if digits.is_empty() {
+                        suffix
+                    } else {
+                        String::new()
+                    },
+                    ..Self::units(block_size)
+                }
+            }
+        };
+        if group {
+            format.separator = thousands_separator();
+        }
+        Ok(format)
+    }
+
+    /// Format `amount` units of `unit` bytes each, e.g. a size in bytes with
+    /// a unit of 1 or a count of 512-byte blocks
+    pub fn format(&self, amount: u64, unit: u64) -> String {
+        // The integer part, tenths and what is left below the tenths:
+        // 0 nothing, 1 less than half, 2 exactly half, 3 more than half
+        let total = u128::from(amount) * u128::from(unit);
+        let block_size = u128::from(self.block_size);
+        let mut whole = total / block_size;
+        let remainder = total % block_size * 10;
+        let mut tenths = remainder / block_size;
+        let below = remainder % block_size;
+        let mut rounding = match (below * 2).cmp(&block_size) {
+            _ if below == 0 => 0,
+            Ordering::Less => 1,
+            Ordering::Equal => 2,
+            Ordering::Greater => 3,
+        };
+
+        let mut exponent = 0;
+        let mut decimal = None;
+        if let Some(base) = self.autoscale.map(u128::from)
+            && whole >= base
+        {
+            while whole >= base && exponent < POWERS_1024.len() {
+                let scaled_tenths = whole % base * 10 + tenths;
+                let half = scaled_tenths % base * 2 + (rounding >> 1);
+                whole /= base;
+                tenths = scaled_tenths / base;
+                rounding = if half < base {
+                    u128::from(half + rounding != 0)
+                } else {
+                    2 + u128::from(base < half + rounding)
+                };
+                exponent += 1;
+            }
+            // Below 10, one decimal is shown
+            if whole < 10 {
+                if rounding > 0 {
+                    tenths += 1;
+                    rounding = 0;
+                    if tenths == 10 {
+                        whole += 1;
+                        tenths = 0;
+                    }
+                }
+                if whole < 10 {
+                    decimal = Some(tenths);
+                    tenths = 0;
+                }
+            }
+        }
+
+        if tenths + rounding > 0 {
+            whole += 1;
+            if self.autoscale.is_some_and(|base| whole == u128::from(base))
+                && exponent < POWERS_1024.len()
+            {
+                whole = 1;
+                exponent += 1;
+                decimal = Some(0);
+            }
+        }
+
+        let mut out = group_thousands(&whole.to_string(), &self.separator);
+        if let Some(tenths) = decimal {
+            out.push_str(&format!(".{}", tenths));
+        }
+        if exponent > 0 {
+            let letters = match self.autoscale {
+                Some(1000) => POWERS_1000,
+                _ => POWERS_1024,
+            };
+            out.push(letters[exponent - 1] as char);
+        }
+        out.push_str(&self.suffix);
+        out
+    }
+}
+
+fn group_thousands(digits: &str, separator: &str) -> String {
+    if separator.is_empty() {
+        return digits.to_string();
+    }
+    let mut out = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            out.push_str(separator);
+        }
+        out.push(digit);
+    }
+    out
+}
+
+/// The thousands separator of LC_NUMERIC, empty in the C locale
+fn thousands_separator() -> String {
+    unsafe {
+        libc::setlocale(libc::LC_NUMERIC, c"".as_ptr());
+        let conv = libc::localeconv();
+        if conv.is_null() || (*conv).thousands_sep.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr((*conv).thousands_sep)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
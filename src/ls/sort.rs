@@ -0,0 +1,250 @@
+use crate::Entry;
+use crate::time::{TimeField, Timestamp};
+use clap::ValueEnum;
+use std::cmp::Ordering;
+use std::ffi::{CString, OsStr};
+use std::os::unix::ffi::OsStrExt;
+
+/// What entries are sorted by (--sort)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum SortKey {
+    /// Directory order, without sorting (-U)
+    None,
+    /// Name, collated by LC_COLLATE
+    Name,
+    /// Size, largest first (-S)
+    Size,
+    /// Modification time, newest first (-t)
+    Time,
+    /// Natural order of version numbers within names (-v)
+    Version,
+    /// Extension, then name (-X)
+    Extension,
+}
+
+/// Use the collation order of LC_COLLATE for name comparisons, as GNU ls does
+pub fn init_collation() {
+    unsafe {
+        libc::setlocale(libc::LC_COLLATE, c"".as_ptr());
+    }
+}
+
+/// Sort entries in place. Ties are always broken by name so the order is
+/// deterministic, and `reverse` reverses the whole order. Sorting by time
+/// uses the timestamp selected by `time`. With
+/// `directories_first`, directories (and links to them) come before other
+/// entries whatever the sort key.
+pub fn sort_entries(
+    entries: &mut Vec<Entry>,
+    key: SortKey,
+    time: TimeField,
+    reverse: bool,
+    directories_first: bool,
+) {
+    let mut groups = if directories_first {
+        let (dirs, others) = entries.drain(..).partition(is_directory);
+        vec![dirs, others]
+    } else {
+        vec![std::mem::take(entries)]
+    };
+
+    for group in &mut groups {
+        if key != SortKey::None {
+            sort_group(group, key, time, reverse);
+        }
+        entries.append(group);
+    }
+}
+
+fn sort_group(group: &mut Vec<Entry>, key: SortKey, time: TimeField, reverse: bool) {
+    // Collation keys are computed once per entry rather than per comparison
+    let mut keyed: Vec<(Vec<u8>, Vec<u8>, Entry)> = group
+        .drain(..)
+        .map(|entry| {
+            let extension_key = if key == SortKey::Extension {
+                collation_key(extension(&entry.name))
+            } else {
+                Vec::new()
+            };
+            (collation_key(&entry.name), extension_key, entry)
+        })
+        .collect();
+
+    keyed.sort_by(|(a_key, a_extension, a), (b_key, b_extension, b)| {
+        let by_name = || a_key.cmp(b_key);
+        let ordering = match key {
+            SortKey::None | SortKey::Name => by_name(),
+            SortKey::Size => size(b).cmp(&size(a)).then_with(by_name),
+            SortKey::Time => timestamp(b, time)
+                .cmp(&timestamp(a, time))
+                .then_with(by_name),
+            SortKey::Extension => a_extension.cmp(b_extension).then_with(by_name),
+            SortKey::Version => version_cmp(a.name.as_bytes(), b.name.as_bytes())
+                .then_with(|| a.name.as_bytes().cmp(b.name.as_bytes())),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+
+    group.extend(keyed.into_iter().map(|(_, _, entry)| entry));
+}
+
+fn size(entry: &Entry) -> u64 {
+    entry.metadata.as_ref().map_or(0, |metadata| metadata.len())
+}
+
+fn timestamp(entry: &Entry, time: TimeField) -> Option<Timestamp> {
+    entry
+        .metadata
+        .as_ref()
+        .and_then(|metadata| time.of(metadata))
+}
+
+/// Whether an entry is a directory or a symlink to one
+pub fn is_directory(entry: &Entry) -> bool {
+    match &entry.metadata {
+        Some(metadata) if metadata.is_dir() => true,
+        Some(metadata) if metadata.file_type().is_symlink() => {
+            crate::stat(&entry.path, true).is_ok_and(|target| target.is_dir())
+        }
+        _ => false,
+    }
+}
+
+/// The part of the name from its last `.`, or nothing when there is none
+fn extension(name: &OsStr) -> &OsStr {
+    let bytes = name.as_bytes();
+    match bytes.iter().rposition(|&b| b == b'.') {
+        Some(index) => OsStr::from_bytes(&bytes[index..]),
+        None => OsStr::new(""),
+    }
+}
+
+/// Transform a name with `strxfrm` so that comparing the results byte by byte
+/// gives the same order as `strcoll` in the current locale
+fn collation_key(name: &OsStr) -> Vec<u8> {
+    let Ok(name) = CString::new(name.as_bytes()) else {
+        return name.as_bytes().to_vec();
+    };
+    let len = unsafe { libc::strxfrm(std::ptr::null_mut(), name.as_ptr(), 0) };
+    let mut key = vec![0u8; len + 1];
+    unsafe {
+        libc::strxfrm(key.as_mut_ptr().cast(), name.as_ptr(), key.len());
+    }
+    key.truncate(len);
+    key
+}
+
+/// Compare names the way GNU `filevercmp` does: runs of digits compare as
+/// numbers, `~` sorts before everything, and file suffixes such as `.tar.gz`
+/// are only considered when the rest of the names are equal
+fn version_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        _ => {}
+    }
+
+    // ".", then "..", then other hidden names sort before everything else
+    match (a[0] == b'.', b[0] == b'.') {
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        (true, true) => {
+            for special in [&b"."[..], b".."] {
+                match (a == special, b == special) {
+                    (true, true) => return Ordering::Equal,
+                    (true, false) => return Ordering::Less,
+                    (false, true) => return Ordering::Greater,
+                    _ => {}
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let a_prefix = suffix_start(a);
+    let b_prefix = suffix_start(b);
+    let ordering = verrevcmp(&a[..a_prefix], &b[..b_prefix]);
+    if ordering != Ordering::Equal || (a_prefix == a.len() && b_prefix == b.len()) {
+        ordering
+    } else {
+        verrevcmp(a, b)
+    }
+}
+
+/// Where the file suffix begins, i.e. the longest match of
+/// `(\.[A-Za-z~][A-Za-z0-9~]*)*$`
+fn suffix_start(s: &[u8]) -> usize {
+    let mut prefix_len = 0;
+    let mut i = 0;
+    while i < s.len() {
+        i += 1;
+        prefix_len = i;
+        while i + 1 < s.len()
+            && s[i] == b'.'
+            && (s[i + 1].is_ascii_alphabetic() || s[i + 1] == b'~')
+        {
+            i += 2;
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'~') {
+                i += 1;
+            }
+        }
+    }
+    prefix_len
+}
+
+/// Sort weight of the byte at `pos`: the end of the string sorts before
+/// anything but `~`, letters before other characters
+fn order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(*c),
+        Some(b'~') => -2,
+        Some(c) => i32::from(*c) + 256,
+    }
+}
+
+/// The version comparison from dpkg, as used by `filevercmp`
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let (a_order, b_order) = (order(a, i), order(b, j));
+            if a_order != b_order {
+                return a_order.cmp(&b_order);
+            }
+            i += 1;
+            j += 1;
+        }
+
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while i < a.len() && j < b.len() && a[i].is_ascii_digit() && b[j].is_ascii_digit() {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if i < a.len() && a[i].is_ascii_digit() {
+            return Ordering::Greater;
+        }
+        if j < b.len() && b[j].is_ascii_digit() {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
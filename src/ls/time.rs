@@ -0,0 +1,181 @@
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Half of an average Gregorian year, the age up to which GNU ls shows the
+/// time of day instead of the year
+const SIX_MONTHS: i64 = 31_556_952 / 2;
+
+/// A point in time as seconds and nanoseconds since the epoch
+pub type Timestamp = (i64, i64);
+
+/// Which timestamp is shown with -l and used for sorting by time (--time)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum TimeField {
+    /// Last modification (the default)
+    #[value(alias = "mtime")]
+    Modification,
+    /// Last access (-u)
+    #[value(aliases = ["atime", "use"])]
+    Access,
+    /// Last status change (-c)
+    #[value(aliases = ["ctime", "status"])]
+    Change,
+    /// Creation, where the file system records it
+    #[value(alias = "creation")]
+    Birth,
+}
+
+impl TimeField {
+    /// The selected timestamp of a file, or `None` when it is not recorded
+    pub fn of(self, metadata: &Metadata) -> Option<Timestamp> {
+        match self {
+            TimeField::Modification => Some((metadata.mtime(), metadata.mtime_nsec())),
+            TimeField::Access => Some((metadata.atime(), metadata.atime_nsec())),
+            TimeField::Change => Some((metadata.ctime(), metadata.ctime_nsec())),
+            TimeField::Birth => metadata.created().ok().map(timestamp),
+        }
+    }
+}
+
+/// How timestamps are written in the long format (--time-style)
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TimeStyle {
+    /// `2024-03-01 14:05:09.123456789 +0000`
+    FullIso,
+    /// `2024-03-01 14:05`
+    LongIso,
+    /// `03-01 14:05` for recent files, `2024-03-01 ` otherwise
+    Iso,
+    /// `Mar  1 14:05` for recent files, `Mar  1  2024` otherwise
+    Locale,
+    /// The age of the file, such as `3 hours ago`
+    Relative,
+    /// `+FORMAT` in strftime syntax; a second format after a newline is used
+    /// for recent files
+    Custom { old: String, recent: String },
+}
+
+impl TimeStyle {
+    /// Parse a --time-style argument. As in GNU ls, a `posix-` prefix only
+    /// takes effect outside the POSIX locale, where the plain locale style is used.
+    pub fn parse(style: &str) -> Result<Self, String> {
+        let style = match style.strip_prefix("posix-") {
+            Some(_) if posix_locale() => "locale",
+            Some(rest) => rest,
+            None => style,
+        };
+        match style {
+            "full-iso" => Ok(TimeStyle::FullIso),
+            "long-iso" => Ok(TimeStyle::LongIso),
+            "iso" => Ok(TimeStyle::Iso),
+            "locale" => Ok(TimeStyle::Locale),
+            "relative" => Ok(TimeStyle::Relative),
+            _ => {
+                let format = style
+                    .strip_prefix('+')
+                    .filter(|format| {
+                        !StrftimeItems::new(format).any(|item| matches!(item, Item::Error))
+                    })
+                    .ok_or_else(|| {
+                        "valid styles are [posix-]full-iso, [posix-]long-iso, [posix-]iso, \
+                         [posix-]locale, relative and +FORMAT (e.g., +%H:%M)"
+                            .to_string()
+                    })?;
+                let (old, recent) = format.split_once('\n').unwrap_or((format, format));
+                Ok(TimeStyle::Custom {
+                    old: old.to_string(),
+                    recent: recent.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Whether the column has a varying width and must be padded to line up
+    pub fn pads(&self) -> bool {
+        *self == TimeStyle::Relative
+    }
+
+    /// Format `time` as seen at `now`. A file counts as recent when it is less
+    /// than six months old and not in the future; unknown times print as `?`.
+    pub fn format(&self, time: Option<Timestamp>, now: Timestamp) -> String {
+        let Some(time) = time else {
+            return "?".to_string();
+        };
+        let Some(date) = DateTime::from_timestamp(time.0, time.1 as u32) else {
+            return time.0.to_string();
+        };
+        let date = date.with_timezone(&Local);
+        let recent = (now.0 - SIX_MONTHS, now.1) < time && time <= now;
+        let format = match self {
+            TimeStyle::FullIso => "%Y-%m-%d %H:%M:%S%.9f %z",
+            TimeStyle::LongIso => "%Y-%m-%d %H:%M",
+            TimeStyle::Iso if recent => "%m-%d %H:%M",
+            TimeStyle::Iso => "%Y-%m-%d ",
+            TimeStyle::Locale if recent => "%b %e %H:%M",
+            TimeStyle::Locale => "%b %e  %Y",
+            TimeStyle::Relative => return relative(now.0 - time.0),
+            TimeStyle::Custom { recent: format, .. } if recent => format,
+            TimeStyle::Custom { old, .. } => old,
+        };
+        date.format(format).to_string()
+    }
+}
+
+/// The current time, for deciding which files are recent
+pub fn now() -> Timestamp {
+    timestamp(SystemTime::now())
+}
+
+fn timestamp(time: SystemTime) -> Timestamp {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, i64::from(since.subsec_nanos())),
+        Err(e) => {
+            let before = e.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (
+                    -(before.as_secs() as i64) - 1,
+                    1_000_000_000 - i64::from(nanos),
+                ),
+            }
+        }
+    }
+}
+
+/// Describe an age in seconds in the largest whole unit, e.g. `3 hours ago`,
+/// or `in 2 days` for times in the future
+fn relative(age: i64) -> String {
+    const UNITS: &[(i64, &str)] = &[
+        (365 * 24 * 60 * 60, "year"),
+        (30 * 24 * 60 * 60, "month"),
+        (7 * 24 * 60 * 60, "week"),
+        (24 * 60 * 60, "day"),
+        (60 * 60, "hour"),
+        (60, "minute"),
+        (1, "second"),
+    ];
+    let seconds = age.unsigned_abs() as i64;
+    let (count, unit) = UNITS
+        .iter()
+        .map(|&(length, unit)| (seconds / length, unit))
+        .find(|&(count, _)| count > 0)
+        .unwrap_or((0, "second"));
+    let plural = if count == 1 { "" } else { "s" };
+    if age < 0 {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+/// Whether LC_TIME selects the POSIX locale
+fn posix_locale() -> bool {
+    ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok().filter(|value| !value.is_empty()))
+        .is_none_or(|locale| locale == "C" || locale == "POSIX")
+}
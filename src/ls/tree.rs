@@ -0,0 +1,58 @@
+use clap::ValueEnum;
+
+/// Characters used to draw the branches of --tree (--charset)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Charset {
+    /// Box-drawing characters
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    /// Plain ASCII, for terminals and files without UTF-8
+    Ascii,
+}
+
+impl Charset {
+    /// The connector in front of an entry; `last` marks the final entry of
+    /// its directory
+    pub fn branch(self, last: bool) -> &'static str {
+        match (self, last) {
+            (Charset::Utf8, false) => "├── ",
+            (Charset::Utf8, true) => "└── ",
+            (Charset::Ascii, false) => "|-- ",
+            (Charset::Ascii, true) => "`-- ",
+        }
+    }
+
+    /// The indentation below an entry, continuing the line to its siblings
+    /// unless it was the last one
+    pub fn indent(self, last: bool) -> &'static str {
+        match (self, last) {
+            (Charset::Utf8, false) => "│   ",
+            (Charset::Ascii, false) => "|   ",
+            (_, true) => "    ",
+        }
+    }
+}
+
+/// Directories and files shown in trees, for the closing summary
+#[derive(Default)]
+pub struct Counts {
+    pub directories: usize,
+    pub files: usize,
+}
+
+impl Counts {
+    /// The summary line, e.g. `2 directories, 1 file`
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {}, {} {}",
+            self.directories,
+            if self.directories == 1 {
+                "directory"
+            } else {
+                "directories"
+            },
+            self.files,
+            if self.files == 1 { "file" } else { "files" }
+        )
+    }
+}
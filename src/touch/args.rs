@@ -1,10 +1,19 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use clap::{ArgAction, Parser};
-use std::fs::File;
-use std::path::PathBuf;
+use glob::Pattern;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
+use crate::date::{self, Offset, TimeStyle, Zone};
+use crate::handle::PathHandle;
+use crate::manifest::{self, ManifestFormat};
+use crate::mode;
+use crate::rules;
+use crate::walk::TreeFilter;
+
 #[derive(Parser)]
 #[command(
     name = "touch",
@@ -20,7 +29,11 @@ pub struct Args {
     pub help: Option<bool>,
 
     /// 要處理的檔案列表
-    #[arg(value_name = "檔案", help = "要變更時間戳的檔案", required = true)]
+    #[arg(
+        value_name = "檔案",
+        help = "要變更時間戳的檔案",
+        required_unless_present_any = ["from_manifest", "rules"]
+    )]
     pub files: Vec<PathBuf>,
 
     /// 只變更存取時間（簡短版本）
@@ -36,8 +49,9 @@ pub struct Args {
         short = 'd',
         long = "date",
         value_name = "字串",
-        help = "使用指定字串表示時間而非目前時間",
-        conflicts_with_all = ["reference", "time_format"]
+        help = "使用指定字串表示時間而非目前時間（可用 @秒數[.奈秒] 指定 Unix 時間），\n與 -r 同時使用時，相對時間（如 '+2 hours'）會套用於參考檔案的時間",
+        allow_hyphen_values = true,
+        conflicts_with = "time_format"
     )]
     pub date: Option<String>,
 
@@ -63,15 +77,15 @@ pub struct Args {
         long = "reference",
         value_name = "FILE",
         help = "使用此檔案的時間而非目前時間",
-        conflicts_with_all = ["date", "time_format"]
+        conflicts_with = "time_format"
     )]
     pub reference: Option<PathBuf>,
 
     /// 使用指定時間而非目前時間
     #[arg(
         short = 't',
-        value_name = "[[CC]YY]MMDDhhmm[.ss]",
-        help = "使用指定時間而非目前時間，\n時間格式與 -d 不同: [[CC]YY]MMDDhhmm[.ss]",
+        value_name = "[[CC]YY]MMDDhhmm[.ss[.nnnnnnnnn]]",
+        help = "使用指定時間而非目前時間，\n時間格式與 -d 不同: [[CC]YY]MMDDhhmm[.ss[.nnnnnnnnn]]",
         conflicts_with_all = ["date", "reference"]
     )]
     pub time_format: Option<String>,
@@ -82,10 +96,352 @@ pub struct Args {
         help = "指定要變更的時間：access、atime、use 或 modify、mtime"
     )]
     pub time: Option<String>,
+
+    /// 指定存取時間
+    #[arg(
+        long = "atime",
+        value_name = "DATE",
+        help = "將存取時間設為指定日期（格式同 -d）；\n未搭配 -d、-r、-t 時，未指定的欄位保持不變",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["modify_only", "shift"]
+    )]
+    pub atime_value: Option<String>,
+
+    /// 指定修改時間
+    #[arg(
+        long = "mtime",
+        value_name = "DATE",
+        help = "將修改時間設為指定日期（格式同 -d）；\n未搭配 -d、-r、-t 時，未指定的欄位保持不變",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["access_only", "shift"]
+    )]
+    pub mtime_value: Option<String>,
+
+    /// 以存取時間作為修改時間
+    #[arg(
+        long = "from-atime",
+        help = "將修改時間設為（變更後的）存取時間",
+        conflicts_with_all = ["from_mtime", "mtime_value", "access_only", "shift"]
+    )]
+    pub from_atime: bool,
+
+    /// 以修改時間作為存取時間
+    #[arg(
+        long = "from-mtime",
+        help = "將存取時間設為（變更後的）修改時間",
+        conflicts_with_all = ["atime_value", "modify_only", "shift"]
+    )]
+    pub from_mtime: bool,
+
+    /// 以各檔案目前的時間戳為基準進行位移
+    #[arg(
+        long = "shift",
+        value_name = "DURATION",
+        help = "將各檔案目前的時間戳位移指定長度，例如 --shift=-2h 或 --shift='+1 day 30 minutes'，\n月與年依日曆計算",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["date", "reference", "time_format"]
+    )]
+    pub shift: Option<String>,
+
+    /// 遞迴處理目錄
+    #[arg(
+        short = 'R',
+        long = "recursive",
+        help = "遞迴處理目錄內的所有項目（不會跟隨目錄內的符號連結，\n搭配 -h 時變更符號連結本身的時間）"
+    )]
+    pub recursive: bool,
+
+    /// 只處理符合樣式的項目
+    #[arg(
+        long = "include",
+        value_name = "GLOB",
+        help = "遞迴時只處理名稱符合樣式的項目，樣式含 / 時比對相對路徑",
+        requires = "recursive"
+    )]
+    pub include: Vec<String>,
+
+    /// 略過符合樣式的項目
+    #[arg(
+        long = "exclude",
+        value_name = "GLOB",
+        help = "遞迴時略過名稱符合樣式的項目，符合的目錄不會再往下走訪",
+        requires = "recursive"
+    )]
+    pub exclude: Vec<String>,
+
+    /// 遞迴的最大深度
+    #[arg(
+        long = "max-depth",
+        value_name = "N",
+        help = "遞迴時最多往下走訪 N 層目錄（0 表示只處理指定的目錄本身）",
+        requires = "recursive"
+    )]
+    pub max_depth: Option<usize>,
+
+    /// 只處理目錄
+    #[arg(
+        long = "dirs-only",
+        help = "遞迴時只變更目錄的時間",
+        requires = "recursive",
+        conflicts_with = "files_only"
+    )]
+    pub dirs_only: bool,
+
+    /// 只處理非目錄的項目
+    #[arg(
+        long = "files-only",
+        help = "遞迴時只變更非目錄項目的時間",
+        requires = "recursive"
+    )]
+    pub files_only: bool,
+
+    /// 將時間戳寫入清單
+    #[arg(
+        long = "dump-manifest",
+        value_name = "FILE",
+        help = "遞迴記錄所指定檔案與目錄的存取和修改時間（奈秒精度）並寫入清單檔案，\n不變更任何時間戳",
+        conflicts_with_all = ["from_manifest", "date", "reference", "time_format", "shift"]
+    )]
+    pub dump_manifest: Option<PathBuf>,
+
+    /// 從清單還原時間戳
+    #[arg(
+        long = "from-manifest",
+        value_name = "FILE",
+        help = "依 --dump-manifest 產生的清單還原各檔案的時間戳",
+        conflicts_with_all = ["files", "date", "reference", "time_format", "shift"]
+    )]
+    pub from_manifest: Option<PathBuf>,
+
+    /// 清單檔案格式
+    #[arg(
+        long = "manifest-format",
+        value_name = "FORMAT",
+        help = "清單格式：json 或 tsv（預設依副檔名判斷）"
+    )]
+    pub manifest_format: Option<String>,
+
+    /// 依規則檔設定時間
+    #[arg(
+        long = "rules",
+        value_name = "FILE",
+        help = "依規則檔遞迴設定所指定目錄（預設為 .）內項目的時間；\n每行為「萬用字元樣式 時間」，例如 src/**/*.rs @1700000000，\n樣式比對相對路徑，多條規則符合時以最後一條為準",
+        conflicts_with_all = ["date", "reference", "time_format", "shift", "reproducible", "dump_manifest", "from_manifest", "atime_value", "mtime_value"]
+    )]
+    pub rules: Option<PathBuf>,
+
+    /// 只調降較新的時間戳
+    #[arg(
+        long = "clamp",
+        help = "只將比指定時間更新的時間戳調降為指定時間，較舊的檔案保持不變",
+        conflicts_with = "shift"
+    )]
+    pub clamp: bool,
+
+    /// 使用 SOURCE_DATE_EPOCH 作為時間
+    #[arg(
+        long = "reproducible",
+        help = "使用環境變數 SOURCE_DATE_EPOCH 指定的時間而非目前時間，\n常與 --clamp 搭配以產生可重現的建置",
+        conflicts_with_all = ["date", "reference", "time_format", "shift"]
+    )]
+    pub reproducible: bool,
+
+    /// 只更新修改時間早於指定時間的檔案
+    #[arg(
+        long = "if-older-than",
+        value_name = "REF|DATE",
+        help = "只在檔案的修改時間早於參考檔案的修改時間或指定日期時才更新，\n不存在的檔案視為較舊",
+        allow_hyphen_values = true
+    )]
+    pub if_older_than: Option<String>,
+
+    /// 只更新修改時間晚於指定時間的檔案
+    #[arg(
+        long = "if-newer-than",
+        value_name = "REF|DATE",
+        help = "只在檔案的修改時間晚於參考檔案的修改時間或指定日期時才更新",
+        allow_hyphen_values = true
+    )]
+    pub if_newer_than: Option<String>,
+
+    /// 建立檔案時一併建立上層目錄
+    #[arg(
+        short = 'p',
+        long = "parents",
+        help = "建立檔案時，視需要建立不存在的上層目錄"
+    )]
+    pub parents: bool,
+
+    /// 新建檔案的權限
+    #[arg(
+        long = "mode",
+        value_name = "MODE",
+        help = "新建立檔案的權限，可使用八進位（如 640）或符號模式（如 u=rw,g=r）"
+    )]
+    pub mode: Option<String>,
+
+    /// 新建檔案的範本
+    #[arg(
+        long = "template",
+        value_name = "FILE",
+        help = "以此檔案的內容作為新建立檔案的初始內容"
+    )]
+    pub template: Option<PathBuf>,
+
+    /// 顯示處理過程
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        help = "顯示每個檔案是否被建立、變更了哪些時間戳，以及略過的檔案與原因"
+    )]
+    pub verbose: bool,
+
+    /// 只顯示將執行的動作
+    #[arg(
+        short = 'n',
+        long = "dry-run",
+        help = "只顯示將建立哪些檔案、變更哪些時間戳，而不實際變更任何檔案"
+    )]
+    pub dry_run: bool,
+
+    /// 輸出時間的格式
+    #[arg(
+        long = "time-style",
+        value_name = "STYLE",
+        default_value = "full-iso",
+        help = "-v 與 -n 顯示時間的格式：full-iso、long-iso、iso 或 +FORMAT"
+    )]
+    pub time_style: String,
+
+    /// 解讀與顯示時間所用的時區
+    #[arg(
+        long = "tz",
+        value_name = "ZONE",
+        help = "以指定的 IANA 時區（如 Asia/Taipei）解讀 -d 與 -t 的時間，而非 TZ 環境變數",
+        conflicts_with = "utc"
+    )]
+    pub tz: Option<String>,
+
+    /// 使用 UTC 解讀時間
+    #[arg(short = 'u', long = "utc", help = "以 UTC 解讀 -d 與 -t 的時間")]
+    pub utc: bool,
 }
 
 impl Args {
-    pub fn touch_files(&self) -> Result<()> {
+    /// 處理所有檔案，單一檔案失敗時仍會繼續處理其餘檔案
+    ///
+    /// 回傳值表示是否所有檔案皆處理成功
+    pub fn touch_files(&self) -> Result<bool> {
+        let plan = self.plan()?;
+        let filter = self.tree_filter()?;
+
+        if let Some(output) = &self.dump_manifest {
+            return self.dump_manifest(output, &filter);
+        }
+        if let Some(input) = &self.from_manifest {
+            return self.restore_manifest(input, &plan);
+        }
+        if let Some(rules_path) = &self.rules {
+            return self.apply_rules(rules_path, &filter, &plan);
+        }
+
+        // 時間來源（-r、-d、-t）的錯誤屬於致命錯誤，在處理任何檔案前回報
+        let times = self.get_times()?;
+
+        let mut all_ok = true;
+        for path in &self.files {
+            let is_dir = if self.no_dereference {
+                path.symlink_metadata()
+            } else {
+                path.metadata()
+            }
+            .is_ok_and(|metadata| metadata.is_dir());
+
+            all_ok &= if self.recursive && is_dir {
+                // 遞迴走訪時只處理既有的項目，不會建立任何檔案
+                filter.walk(path, &mut |entry, depth| {
+                    report(self.touch_file(entry, times, &plan, Target::walked(depth)))
+                })
+            } else {
+                report(self.touch_file(path, times, &plan, Target::Operand))
+            };
+        }
+        Ok(all_ok)
+    }
+
+    /// 遞迴記錄所有檔案的時間戳並寫入清單
+    fn dump_manifest(&self, output: &Path, filter: &TreeFilter) -> Result<bool> {
+        let format = ManifestFormat::detect(output, self.manifest_format.as_deref())?;
+
+        let mut entries = Vec::new();
+        let mut all_ok = true;
+        for path in &self.files {
+            all_ok &= filter.walk(path, &mut |entry, depth| {
+                let metadata = if self.no_dereference || depth > 0 {
+                    entry.symlink_metadata()
+                } else {
+                    entry.metadata()
+                };
+                report(
+                    metadata
+                        .map(|metadata| entries.push(manifest::entry_for(entry, &metadata)))
+                        .map_err(|e| {
+                            anyhow::anyhow!(
+                                "failed to get attributes of '{}': {}",
+                                entry.display(),
+                                io_error_message(&e)
+                            )
+                        }),
+                )
+            });
+        }
+
+        manifest::write_manifest(output, format, &entries)?;
+        Ok(all_ok)
+    }
+
+    /// 依清單還原各檔案的時間戳，清單中已不存在的檔案不會被建立，而是回報錯誤
+    fn restore_manifest(&self, input: &Path, plan: &Plan) -> Result<bool> {
+        let format = ManifestFormat::detect(input, self.manifest_format.as_deref())?;
+
+        let mut all_ok = true;
+        for entry in manifest::read_manifest(input, format)? {
+            let times = TimeSpec::Fixed(entry.atime.into(), entry.mtime.into());
+            all_ok &= report(self.touch_file(&entry.path, times, plan, Target::Listed));
+        }
+        Ok(all_ok)
+    }
+
+    /// 走訪各目錄一次，依規則檔為符合樣式的項目設定對應的時間
+    fn apply_rules(&self, rules_path: &Path, filter: &TreeFilter, plan: &Plan) -> Result<bool> {
+        let rules = rules::read_rules(rules_path, self.zone()?)?;
+        let default_root = [PathBuf::from(".")];
+        let roots = if self.files.is_empty() {
+            &default_root[..]
+        } else {
+            &self.files[..]
+        };
+
+        let mut all_ok = true;
+        for root in roots {
+            all_ok &= filter.walk(root, &mut |entry, depth| {
+                let relative = entry.strip_prefix(root).unwrap_or(entry);
+                match rules::time_for(&rules, relative) {
+                    Some(time) => report(self.touch_file(
+                        entry,
+                        TimeSpec::Fixed(time, time),
+                        plan,
+                        Target::walked(depth),
+                    )),
+                    None => true,
+                }
+            });
+        }
+        Ok(all_ok)
+    }
+
+    /// 整理與時間來源無關、每個檔案共用的選項
+    fn plan(&self) -> Result<Plan> {
         let (mut access_only, mut modify_only) = (self.access_only, self.modify_only);
 
         if let Some(time_word) = &self.time {
@@ -96,97 +452,489 @@ impl Args {
             }
         }
 
-        for path in &self.files {
-            if !path.exists() && self.no_create {
-                continue;
+        let zone = self.zone()?;
+        let umask = current_umask();
+        let mode = self
+            .mode
+            .as_deref()
+            .map(|spec| mode::parse_mode(spec, 0o666 & !umask, umask))
+            .transpose()?;
+        let template = self
+            .template
+            .as_ref()
+            .map(|path| {
+                fs::read(path).with_context(|| format!("failed to read template {:?}", path))
+            })
+            .transpose()?;
+
+        Ok(Plan {
+            access_only,
+            modify_only,
+            parents: self.parents,
+            mode,
+            template,
+            time_style: TimeStyle::parse(&self.time_style)?,
+            zone,
+            older_than: self
+                .if_older_than
+                .as_deref()
+                .map(|value| resolve_threshold(value, zone))
+                .transpose()?,
+            newer_than: self
+                .if_newer_than
+                .as_deref()
+                .map(|value| resolve_threshold(value, zone))
+                .transpose()?,
+        })
+    }
+
+    fn zone(&self) -> Result<Zone> {
+        Zone::parse(self.tz.as_deref(), self.utc)
+    }
+
+    fn tree_filter(&self) -> Result<TreeFilter> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Pattern::new(p).with_context(|| format!("無效的萬用字元樣式: {}", p)))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(TreeFilter {
+            include: compile(&self.include)?,
+            exclude: compile(&self.exclude)?,
+            max_depth: self.max_depth,
+            dirs_only: self.dirs_only,
+            files_only: self.files_only,
+            symlinks: self.no_dereference,
+        })
+    }
+
+    /// 開啟要變更時間戳的項目，`-` 表示標準輸出所對應的檔案
+    ///
+    /// 走訪目錄時找到的項目一律不跟隨符號連結，避免變更目錄樹以外的檔案
+    fn open_handle(&self, path: &Path, target: Target) -> io::Result<PathHandle> {
+        if path == Path::new("-") {
+            PathHandle::stdout()
+        } else {
+            PathHandle::open(path, !self.no_dereference && target != Target::Walked)
+        }
+    }
+
+    /// 變更單一檔案的時間戳，失敗時回傳 GNU 風格的錯誤訊息
+    fn touch_file(&self, path: &Path, times: TimeSpec, plan: &Plan, target: Target) -> Result<()> {
+        let create = target == Target::Operand;
+        // 以 O_PATH 開啟，不會為了讀寫而開啟 FIFO 或裝置；使用 -h 時開啟符號連結本身，
+        // 因此懸空連結也視為存在
+        let existing = self.open_handle(path, target).and_then(|handle| {
+            let times = handle.times()?;
+            Ok((handle, times))
+        });
+
+        // 不存在的檔案視為比任何時間都舊
+        let current_mtime = existing.as_ref().ok().map(|(_, (_, mtime))| *mtime);
+        if let Some(reason) = plan.skip_reason(current_mtime) {
+            if self.verbose || self.dry_run {
+                println!("skipped '{}': {}", path.display(), reason);
             }
+            return Ok(());
+        }
 
-            let (atime, mtime) = self.get_times(path)?;
+        // 取得檔案目前的時間戳；不存在的檔案會先建立（--dry-run 時以目前時間代替）
+        let (handle, created, current_atime, current_mtime) = match existing {
+            Ok((handle, (atime, mtime))) => (Some(handle), false, atime, mtime),
+            // 只有「不存在」可以略過；符號連結迴圈、路徑中有非目錄等錯誤仍須回報
+            Err(e)
+                if e.kind() == io::ErrorKind::NotFound && target.skips_missing(self.no_create) =>
+            {
+                return Ok(());
+            }
+            Err(e) if self.no_dereference || self.no_create || !create => anyhow::bail!(
+                "setting times of '{}': {}",
+                path.display(),
+                io_error_message(&e)
+            ),
+            Err(_) if self.dry_run => {
+                let now = filetime::FileTime::now();
+                (None, true, now, now)
+            }
+            Err(_) => {
+                let (handle, (atime, mtime)) = plan
+                    .create_file(path)
+                    .and_then(|_| self.open_handle(path, target))
+                    .and_then(|handle| {
+                        let times = handle.times()?;
+                        Ok((handle, times))
+                    })
+                    .map_err(|e| {
+                        anyhow::anyhow!(
+                            "cannot touch '{}': {}",
+                            path.display(),
+                            io_error_message(&e)
+                        )
+                    })?;
+                (Some(handle), true, atime, mtime)
+            }
+        };
 
-            let atime = if modify_only {
-                filetime::FileTime::from_last_access_time(&path.metadata()?)
-            } else {
-                atime
-            };
+        let (atime, mtime) = match times {
+            TimeSpec::Fixed(atime, mtime) => (atime, mtime),
+            TimeSpec::Fields { atime, mtime } => (
+                atime.unwrap_or(current_atime),
+                mtime.unwrap_or(current_mtime),
+            ),
+            TimeSpec::Shift(offset) => (
+                date::offset_file_time(current_atime, offset, plan.zone)
+                    .with_context(|| format!("setting times of '{}'", path.display()))?,
+                date::offset_file_time(current_mtime, offset, plan.zone)
+                    .with_context(|| format!("setting times of '{}'", path.display()))?,
+            ),
+        };
 
-            let mtime = if access_only {
-                filetime::FileTime::from_last_modification_time(&path.metadata()?)
-            } else {
-                mtime
-            };
+        let atime = if plan.modify_only {
+            current_atime
+        } else {
+            atime
+        };
+        let mtime = if plan.access_only {
+            current_mtime
+        } else {
+            mtime
+        };
 
-            if self.no_dereference {
-                filetime::set_symlink_file_times(path, atime, mtime)
-                    .with_context(|| format!("failed to set times for symlink {:?}", path))?;
-            } else {
-                if !path.exists() {
-                    File::create(path)
-                        .with_context(|| format!("failed to create file {:?}", path))?;
+        let (atime, mtime) = if self.from_atime {
+            (atime, atime)
+        } else if self.from_mtime {
+            (mtime, mtime)
+        } else {
+            (atime, mtime)
+        };
+
+        // --clamp 只會把較新的時間戳調降為指定時間，較舊的檔案完全不變動
+        let (atime, mtime) = if self.clamp {
+            (atime.min(current_atime), mtime.min(current_mtime))
+        } else {
+            (atime, mtime)
+        };
+        let unchanged = atime == current_atime && mtime == current_mtime;
+
+        if self.verbose || self.dry_run {
+            let prefix = if self.dry_run { "would " } else { "" };
+            let change = |name: &str, old, new| {
+                if old == new {
+                    format!("{} unchanged", name)
+                } else {
+                    format!(
+                        "{} {} -> {}",
+                        name,
+                        plan.format_time(old),
+                        plan.format_time(new)
+                    )
                 }
-                filetime::set_file_times(path, atime, mtime)
-                    .with_context(|| format!("failed to set times for {:?}", path))?;
+            };
+            if created {
+                println!(
+                    "{}create '{}': atime {}, mtime {}",
+                    prefix,
+                    path.display(),
+                    plan.format_time(atime),
+                    plan.format_time(mtime)
+                );
+            } else if unchanged && self.clamp {
+                println!("unchanged '{}'", path.display());
+            } else {
+                println!(
+                    "{}update '{}': {}, {}",
+                    prefix,
+                    path.display(),
+                    change("atime", current_atime, atime),
+                    change("mtime", current_mtime, mtime)
+                );
             }
         }
+
+        if self.dry_run || (unchanged && self.clamp) {
+            return Ok(());
+        }
+        let Some(handle) = handle else {
+            return Ok(());
+        };
+
+        let result = handle.set_times(atime, mtime);
+        result.map_err(|e| match e.raw_os_error() {
+            Some(libc::EOVERFLOW | libc::ERANGE) => anyhow::anyhow!(
+                "setting times of '{}': the file system cannot represent atime {} or mtime {}",
+                path.display(),
+                plan.format_time(atime),
+                plan.format_time(mtime)
+            ),
+            _ => anyhow::anyhow!(
+                "setting times of '{}': {}",
+                path.display(),
+                io_error_message(&e)
+            ),
+        })?;
+
+        // 部分檔案系統會默默截斷超出範圍的時間，因此回讀修改時間確認（容許 FAT 的兩秒精度）。
+        // 存取時間不檢查：FAT 只記錄日期，其他行程讀取檔案時也會改變它
+        let (_, stored_mtime) = handle.times().map_err(|e| {
+            anyhow::anyhow!(
+                "failed to get attributes of '{}': {}",
+                path.display(),
+                io_error_message(&e)
+            )
+        })?;
+        if mtime.unix_seconds().abs_diff(stored_mtime.unix_seconds()) > 2 {
+            anyhow::bail!(
+                "setting times of '{}': the file system cannot represent mtime {} (stored as {})",
+                path.display(),
+                plan.format_time(mtime),
+                plan.format_time(stored_mtime)
+            );
+        }
         Ok(())
     }
 
-    fn get_times(&self, _path: &PathBuf) -> Result<(filetime::FileTime, filetime::FileTime)> {
+    fn get_times(&self) -> Result<TimeSpec> {
+        if self.atime_value.is_none()
+            && self.mtime_value.is_none()
+            && !self.from_atime
+            && !self.from_mtime
+        {
+            return self.source_times();
+        }
+
+        // 個別指定欄位時，沒有其他時間來源的欄位保持不變
+        let has_source = self.date.is_some()
+            || self.reference.is_some()
+            || self.time_format.is_some()
+            || self.reproducible;
+        let (atime, mtime) = match self.source_times()? {
+            TimeSpec::Fixed(atime, mtime) if has_source => (Some(atime), Some(mtime)),
+            _ => (None, None),
+        };
+        let zone = self.zone()?;
+        let parse = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|date_str| date::parse_date_string(date_str, zone))
+                .transpose()
+        };
+        Ok(TimeSpec::Fields {
+            atime: parse(&self.atime_value)?.or(atime),
+            mtime: parse(&self.mtime_value)?.or(mtime),
+        })
+    }
+
+    /// 依 --shift、--reproducible、-r、-d、-t 決定時間，皆未指定時使用目前時間
+    fn source_times(&self) -> Result<TimeSpec> {
+        if let Some(shift_str) = &self.shift {
+            let offset = date::parse_relative_offset(shift_str)
+                .with_context(|| format!("無效的時間位移: {}", shift_str))?;
+            return Ok(TimeSpec::Shift(offset));
+        }
+
+        if self.reproducible {
+            let epoch = std::env::var("SOURCE_DATE_EPOCH")
+                .context("使用 --reproducible 時必須設定 SOURCE_DATE_EPOCH")?;
+            let seconds: i64 = epoch
+                .trim()
+                .parse()
+                .with_context(|| format!("無效的 SOURCE_DATE_EPOCH: {}", epoch))?;
+            let ft = filetime::FileTime::from_unix_time(seconds, 0);
+            return Ok(TimeSpec::Fixed(ft, ft));
+        }
+
         if let Some(reference_path) = &self.reference {
             let metadata = std::fs::metadata(reference_path)
                 .with_context(|| format!("failed to get metadata of {:?}", reference_path))?;
-            Ok((
-                filetime::FileTime::from_last_access_time(&metadata),
-                filetime::FileTime::from_last_modification_time(&metadata),
-            ))
+            let atime = filetime::FileTime::from_last_access_time(&metadata);
+            let mtime = filetime::FileTime::from_last_modification_time(&metadata);
+
+            // -d 的相對時間以參考檔案的時間為基準，分別套用於存取與修改時間
+            let Some(date_str) = &self.date else {
+                return Ok(TimeSpec::Fixed(atime, mtime));
+            };
+            match date::parse_relative_offset(date_str) {
+                Some(offset) => {
+                    let zone = self.zone()?;
+                    Ok(TimeSpec::Fixed(
+                        date::offset_file_time(atime, offset, zone)?,
+                        date::offset_file_time(mtime, offset, zone)?,
+                    ))
+                }
+                None => {
+                    let ft = date::parse_date_string(date_str, self.zone()?)?;
+                    Ok(TimeSpec::Fixed(ft, ft))
+                }
+            }
         } else if let Some(date_str) = &self.date {
-            let dt = parse_date_string(date_str)?;
-            let ft = filetime::FileTime::from_system_time(dt.into());
-            Ok((ft, ft))
+            let ft = date::parse_date_string(date_str, self.zone()?)?;
+            Ok(TimeSpec::Fixed(ft, ft))
         } else if let Some(time_str) = &self.time_format {
-            let dt = parse_time_format(time_str)?;
-            let ft = filetime::FileTime::from_system_time(dt.into());
-            Ok((ft, ft))
+            let ft = date::parse_time_format(time_str, self.zone()?)?;
+            Ok(TimeSpec::Fixed(ft, ft))
         } else {
-            let now = SystemTime::now();
-            Ok((
-                filetime::FileTime::from_system_time(now),
-                filetime::FileTime::from_system_time(now),
-            ))
+            let now = filetime::FileTime::from_system_time(SystemTime::now());
+            Ok(TimeSpec::Fixed(now, now))
         }
     }
 }
 
-fn parse_date_string(date_str: &str) -> Result<DateTime<Local>> {
-    // Chrono's `parse_from_str` is quite powerful and can handle many formats.
-    // We might need to add more specific format strings if needed.
-    DateTime::parse_from_rfc3339(date_str)
-        .map(|dt| dt.with_timezone(&Local))
-        .or_else(|_| {
-            NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S")
-                .map(|ndt| Local.from_local_datetime(&ndt).single().unwrap())
-        })
-        .with_context(|| format!("無法解析日期字串: {}", date_str))
+/// 輸出單一檔案的錯誤訊息，回傳是否成功
+fn report(result: Result<()>) -> bool {
+    match result {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("touch: {}", err);
+            false
+        }
+    }
+}
+
+/// 取得條件比較用的時間：值為既有檔案時使用其修改時間，否則解析為日期字串
+fn resolve_threshold(value: &str, zone: Zone) -> Result<filetime::FileTime> {
+    match std::fs::metadata(value) {
+        Ok(metadata) => Ok(filetime::FileTime::from_last_modification_time(&metadata)),
+        Err(_) => date::parse_date_string(value, zone),
+    }
+}
+
+fn current_umask() -> u32 {
+    // umask(2) 只能在設定的同時讀取，因此讀取後立即還原
+    unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask as u32
+    }
+}
+
+/// 處理每個檔案時共用、與時間來源無關的選項
+struct Plan {
+    access_only: bool,
+    modify_only: bool,
+    /// 只更新修改時間早於此時間的檔案
+    older_than: Option<filetime::FileTime>,
+    /// 只更新修改時間晚於此時間的檔案
+    newer_than: Option<filetime::FileTime>,
+    /// 建立檔案時一併建立上層目錄
+    parents: bool,
+    /// 新建檔案的權限，不受 umask 影響
+    mode: Option<u32>,
+    /// 新建檔案的初始內容
+    template: Option<Vec<u8>>,
+    /// --verbose 與 --dry-run 輸出時間的格式
+    time_style: TimeStyle,
+    zone: Zone,
+}
+
+impl Plan {
+    fn format_time(&self, time: filetime::FileTime) -> String {
+        self.time_style.format(time, self.zone)
+    }
+
+    /// 建立不存在的檔案，依選項建立上層目錄、寫入範本內容並設定權限
+    fn create_file(&self, path: &Path) -> io::Result<()> {
+        if self.parents
+            && let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)?;
+        }
+
+        // O_NONBLOCK 避免在競爭條件下開啟到 FIFO 時阻塞，O_NOCTTY 避免取得控制終端機
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(path)?;
+        if let Some(content) = &self.template {
+            file.write_all(content)?;
+        }
+        if let Some(mode) = self.mode {
+            file.set_permissions(fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    /// 檢查檔案目前的修改時間是否符合 --if-older-than 與 --if-newer-than，
+    /// 不符合時回傳略過的原因
+    fn skip_reason(&self, mtime: Option<filetime::FileTime>) -> Option<String> {
+        if let (Some(threshold), Some(mtime)) = (self.older_than, mtime)
+            && mtime >= threshold
+        {
+            return Some(format!(
+                "modification time {} is not older than {}",
+                self.format_time(mtime),
+                self.format_time(threshold)
+            ));
+        }
+        if let Some(threshold) = self.newer_than {
+            match mtime {
+                None => return Some("file does not exist".to_string()),
+                Some(mtime) if mtime <= threshold => {
+                    return Some(format!(
+                        "modification time {} is not newer than {}",
+                        self.format_time(mtime),
+                        self.format_time(threshold)
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+        None
+    }
 }
 
-fn parse_time_format(time_str: &str) -> Result<DateTime<Local>> {
-    let format = if time_str.contains('.') {
-        "%Y%m%d%H%M.%S"
-    } else {
-        "%Y%m%d%H%M"
-    };
-
-    let year_str;
-    let (year_part, time_part) = if time_str.len() > 12 {
-        time_str.split_at(4)
-    } else {
-        let now = Local::now();
-        year_str = now.format("%Y").to_string();
-        (year_str.as_str(), time_str)
-    };
-
-    let full_time_str = format!("{}{}", year_part, time_part);
-
-    NaiveDateTime::parse_from_str(&full_time_str, format)
-        .map(|ndt| Local.from_local_datetime(&ndt).single().unwrap())
-        .with_context(|| format!("無法解析時間格式: {}", time_str))
+/// 要變更時間戳的項目從何而來，決定是否建立檔案與是否跟隨符號連結
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// 命令列上的檔案：不存在時建立（-c 除外）
+    Operand,
+    /// 清單或走訪起點所指定的既有檔案：不會建立
+    Listed,
+    /// 走訪目錄時找到的項目：不會建立，也不會跟隨符號連結
+    Walked,
+}
+
+impl Target {
+    /// 走訪起點（深度 0）視同指定的既有檔案，其下的項目為走訪所得
+    fn walked(depth: usize) -> Self {
+        if depth == 0 {
+            Self::Listed
+        } else {
+            Self::Walked
+        }
+    }
+
+    /// 項目不存在時是否直接略過：-c 的檔案與走訪途中消失的項目會略過，
+    /// 清單所列的檔案則應該存在
+    fn skips_missing(self, no_create: bool) -> bool {
+        match self {
+            Self::Operand => no_create,
+            Self::Listed => false,
+            Self::Walked => true,
+        }
+    }
+}
+
+/// 要套用到檔案的時間
+#[derive(Clone, Copy)]
+enum TimeSpec {
+    /// 固定的存取時間與修改時間
+    Fixed(filetime::FileTime, filetime::FileTime),
+    /// 個別指定的存取時間與修改時間，`None` 表示保持不變
+    Fields {
+        atime: Option<filetime::FileTime>,
+        mtime: Option<filetime::FileTime>,
+    },
+    /// 以各檔案目前的時間戳為基準加上位移
+    Shift(Offset),
+}
+
+/// 將 I/O 錯誤轉為不含 "(os error N)" 後綴的訊息，與 GNU touch 的輸出一致
+pub fn io_error_message(err: &io::Error) -> String {
+    let message = err.to_string();
+    match message.find(" (os error ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
 }
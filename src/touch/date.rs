@@ -0,0 +1,382 @@
+use anyhow::{Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{
+    DateTime, Datelike, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone,
+    Utc,
+};
+use chrono_tz::Tz;
+use filetime::FileTime;
+use std::time::SystemTime;
+
+/// 解析與顯示時間時使用的時區
+#[derive(Clone, Copy)]
+pub enum Zone {
+    /// 行程環境（TZ）決定的本地時區
+    Local,
+    Utc,
+    /// IANA 時區名稱，例如 Asia/Taipei
+    Named(Tz),
+}
+
+impl Zone {
+    /// 依 --tz 與 -u/--utc 決定時區，皆未指定時使用本地時區
+    pub fn parse(tz: Option<&str>, utc: bool) -> Result<Self> {
+        match tz {
+            _ if utc => Ok(Self::Utc),
+            None => Ok(Self::Local),
+            Some(name) => name
+                .parse::<Tz>()
+                .map(Self::Named)
+                .map_err(|_| anyhow::anyhow!("無效的時區: {}", name)),
+        }
+    }
+
+    /// 將此時區的本地時間轉為時間戳；遇到夏令時間重疊時取較早者
+    fn resolve(&self, ndt: &NaiveDateTime) -> Option<FileTime> {
+        let dt = match self {
+            Self::Local => Local.from_local_datetime(ndt).earliest()?.to_utc(),
+            Self::Utc => Utc.from_utc_datetime(ndt),
+            Self::Named(tz) => tz.from_local_datetime(ndt).earliest()?.to_utc(),
+        };
+        Some(file_time_from_datetime(dt))
+    }
+
+    /// 時間戳在此時區的本地時間
+    fn local(&self, dt: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Local => dt.with_timezone(&Local).naive_local(),
+            Self::Utc => dt.naive_utc(),
+            Self::Named(tz) => dt.with_timezone(tz).naive_local(),
+        }
+    }
+
+    fn current_year(&self) -> i32 {
+        match self {
+            Self::Local => Local::now().year(),
+            Self::Utc => Utc::now().year(),
+            Self::Named(tz) => Utc::now().with_timezone(tz).year(),
+        }
+    }
+
+    fn format(&self, dt: DateTime<Utc>, format: &str) -> String {
+        match self {
+            Self::Local => dt.with_timezone(&Local).format(format).to_string(),
+            Self::Utc => dt.format(format).to_string(),
+            Self::Named(tz) => dt.with_timezone(tz).format(format).to_string(),
+        }
+    }
+}
+
+/// 解析 -d 的日期字串，相對時間（如 "+2 hours"、"3 days ago"）以目前時間為基準
+///
+/// 支援 `@秒數[.奈秒]` 形式的 Unix 時間；不含時區資訊的日期以 `zone` 解讀
+pub fn parse_date_string(date_str: &str, zone: Zone) -> Result<FileTime> {
+    if let Some(epoch) = date_str.strip_prefix('@') {
+        return parse_epoch(epoch).with_context(|| format!("無法解析日期字串: {}", date_str));
+    }
+
+    if let Some(offset) = parse_relative_offset(date_str) {
+        let now = FileTime::from_system_time(SystemTime::now());
+        return offset_file_time(now, offset, zone);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(date_str) {
+        return Ok(file_time_from_datetime(dt.to_utc()));
+    }
+
+    let ndt = NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S%.f")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(date_str, "%Y-%m-%d")
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .with_context(|| format!("無法解析日期字串: {}", date_str))?;
+    zone.resolve(&ndt)
+        .with_context(|| format!("時間在指定的時區中不存在: {}", date_str))
+}
+
+/// 解析 -t 的 [[CC]YY]MMDDhhmm[.ss[.nnnnnnnnn]] 格式，以 `zone` 解讀
+///
+/// 只有兩位數的年份依 POSIX 規則解讀：69–99 為 19YY，00–68 為 20YY
+pub fn parse_time_format(time_str: &str, zone: Zone) -> Result<FileTime> {
+    parse_time_fields(time_str)
+        .and_then(|(year, rest, second, nanos)| {
+            let year = year.unwrap_or_else(|| zone.current_year());
+            let field = |range: std::ops::Range<usize>| rest[range].parse::<u32>().ok();
+            NaiveDate::from_ymd_opt(year, field(0..2)?, field(2..4)?)?.and_hms_nano_opt(
+                field(4..6)?,
+                field(6..8)?,
+                second,
+                nanos,
+            )
+        })
+        .with_context(|| format!("無法解析時間格式: {}", time_str))
+        .and_then(|ndt| {
+            zone.resolve(&ndt)
+                .with_context(|| format!("時間在指定的時區中不存在: {}", time_str))
+        })
+}
+
+/// 拆出 -t 的年份、MMDDhhmm、秒與奈秒
+fn parse_time_fields(time_str: &str) -> Option<(Option<i32>, &str, u32, u32)> {
+    let (digits, seconds) = match time_str.split_once('.') {
+        Some((digits, seconds)) => (digits, Some(seconds)),
+        None => (time_str, None),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let (year, rest) = match digits.len() {
+        8 => (None, digits),
+        10 => {
+            let yy: i32 = digits[..2].parse().ok()?;
+            (
+                Some(if yy < 69 { 2000 + yy } else { 1900 + yy }),
+                &digits[2..],
+            )
+        }
+        12 => (Some(digits[..4].parse().ok()?), &digits[4..]),
+        _ => return None,
+    };
+
+    let (second, nanos) = match seconds {
+        None => (0, 0),
+        Some(seconds) => {
+            let (whole, fraction) = match seconds.split_once(['.', ',']) {
+                Some((whole, fraction)) => (whole, parse_fraction(fraction)?),
+                None => (seconds, 0),
+            };
+            if whole.len() != 2 || !whole.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            (whole.parse().ok()?, fraction)
+        }
+    };
+    Some((year, rest, second, nanos))
+}
+
+/// 解析 `秒數[.小數]` 形式的 Unix 時間，允許負值
+fn parse_epoch(epoch: &str) -> Option<FileTime> {
+    let (negative, unsigned) = match epoch.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, epoch.strip_prefix('+').unwrap_or(epoch)),
+    };
+    let (whole, fraction) = match unsigned.split_once(['.', ',']) {
+        Some((whole, fraction)) => (whole, parse_fraction(fraction)?),
+        None => (unsigned, 0),
+    };
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let seconds: i64 = whole.parse().ok()?;
+
+    // 負的時間戳以「向下取整的秒數 + 非負奈秒」表示
+    Some(match (negative, fraction) {
+        (false, _) => FileTime::from_unix_time(seconds, fraction),
+        (true, 0) => FileTime::from_unix_time(-seconds, 0),
+        (true, _) => FileTime::from_unix_time(-seconds - 1, 1_000_000_000 - fraction),
+    })
+}
+
+/// 解析最多九位數的小數秒，回傳奈秒
+fn parse_fraction(fraction: &str) -> Option<u32> {
+    if fraction.is_empty() || fraction.len() > 9 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    format!("{:0<9}", fraction).parse().ok()
+}
+
+/// 直接由秒數與奈秒建立時間戳，避免經過 SystemTime 而受平台範圍限制
+fn file_time_from_datetime(dt: DateTime<Utc>) -> FileTime {
+    FileTime::from_unix_time(dt.timestamp(), dt.timestamp_subsec_nanos())
+}
+
+/// 相對時間位移：月與年依日曆計算，其餘單位為固定長度
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Offset {
+    /// 位移的月數，一年計為 12 個月
+    pub months: i64,
+    pub delta: TimeDelta,
+}
+
+/// 解析相對時間，例如 "+2 hours"、"-1 day"、"+1 month"、"1 hour 30 minutes ago" 或 "2h30m"
+///
+/// 字串不是相對時間時回傳 `None`
+pub fn parse_relative_offset(input: &str) -> Option<Offset> {
+    let mut tokens = tokenize(input)?.into_iter().peekable();
+    let mut total = Offset {
+        months: 0,
+        delta: TimeDelta::zero(),
+    };
+    let mut matched = false;
+
+    while let Some(token) = tokens.next() {
+        let (count, unit) = match token {
+            Token::Number(count) => match tokens.next() {
+                Some(Token::Word(unit)) => (count, unit),
+                _ => return None,
+            },
+            Token::Word(word) => match word.as_str() {
+                "ago" if matched && tokens.peek().is_none() => {
+                    total = Offset {
+                        months: total.months.checked_neg()?,
+                        delta: -total.delta,
+                    };
+                    break;
+                }
+                "now" | "today" => (0, "second".to_string()),
+                "yesterday" => (-1, "day".to_string()),
+                "tomorrow" => (1, "day".to_string()),
+                _ => (1, word),
+            },
+        };
+        match unit_months(&unit) {
+            Some(months) => {
+                total.months = total.months.checked_add(count.checked_mul(months)?)?;
+            }
+            None => {
+                let seconds = unit_seconds(&unit)?;
+                // 超出範圍的位移視為無效，而不是溢位
+                total.delta = total
+                    .delta
+                    .checked_add(&TimeDelta::try_seconds(count.checked_mul(seconds)?)?)?;
+            }
+        }
+        matched = true;
+    }
+
+    matched.then_some(total)
+}
+
+/// 將時間戳加上位移，保留奈秒部分
+///
+/// 月與年在 `zone` 的本地時間上依日曆加減，日期超出該月天數時取該月最後一天
+/// （例如 1 月 31 日加一個月為 2 月底），其餘部分再以固定長度加上
+pub fn offset_file_time(time: FileTime, offset: Offset, zone: Zone) -> Result<FileTime> {
+    let time = if offset.months == 0 {
+        time
+    } else {
+        DateTime::from_timestamp(time.unix_seconds(), time.nanoseconds())
+            .and_then(|dt| {
+                let local = zone.local(dt);
+                let months = Months::new(u32::try_from(offset.months.unsigned_abs()).ok()?);
+                let shifted = if offset.months > 0 {
+                    local.checked_add_months(months)
+                } else {
+                    local.checked_sub_months(months)
+                }?;
+                zone.resolve(&shifted)
+            })
+            .context("位移後的時間超出可表示的範圍")?
+    };
+
+    let delta = offset.delta;
+    let nanos = i64::from(time.nanoseconds()) + i64::from(delta.subsec_nanos());
+    let seconds = time
+        .unix_seconds()
+        .saturating_add(delta.num_seconds())
+        .saturating_add(nanos.div_euclid(1_000_000_000));
+    Ok(FileTime::from_unix_time(
+        seconds,
+        nanos.rem_euclid(1_000_000_000) as u32,
+    ))
+}
+
+enum Token {
+    Number(i64),
+    Word(String),
+}
+
+fn tokenize(input: &str) -> Option<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '+' || c == '-' || c.is_ascii_digit() {
+            let mut number = String::new();
+            if c == '+' || c == '-' {
+                number.push(c);
+                chars.next();
+            }
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                number.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Number(number.parse().ok()?));
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+            while let Some(&w) = chars.peek().filter(|w| w.is_alphabetic()) {
+                word.extend(w.to_lowercase());
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        } else {
+            return None;
+        }
+    }
+
+    Some(tokens)
+}
+
+/// 依日曆計算的單位所代表的月數
+fn unit_months(unit: &str) -> Option<i64> {
+    match unit {
+        "month" | "months" => Some(1),
+        "year" | "years" => Some(12),
+        _ => None,
+    }
+}
+
+fn unit_seconds(unit: &str) -> Option<i64> {
+    match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60),
+        "h" | "hour" | "hours" => Some(60 * 60),
+        "d" | "day" | "days" => Some(24 * 60 * 60),
+        "w" | "week" | "weeks" => Some(7 * 24 * 60 * 60),
+        "fortnight" | "fortnights" => Some(14 * 24 * 60 * 60),
+        _ => None,
+    }
+}
+
+/// --time-style 指定的時間顯示格式
+pub enum TimeStyle {
+    FullIso,
+    LongIso,
+    Iso,
+    /// `+FORMAT`，使用 strftime 格式
+    Custom(String),
+}
+
+impl TimeStyle {
+    pub fn parse(style: &str) -> Result<Self> {
+        match style {
+            "full-iso" => Ok(Self::FullIso),
+            "long-iso" => Ok(Self::LongIso),
+            "iso" => Ok(Self::Iso),
+            _ => match style.strip_prefix('+') {
+                Some(format)
+                    if !StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) =>
+                {
+                    Ok(Self::Custom(format.to_string()))
+                }
+                _ => anyhow::bail!("無效的時間格式: {}", style),
+            },
+        }
+    }
+
+    pub fn format(&self, time: FileTime, zone: Zone) -> String {
+        let format = match self {
+            Self::FullIso => "%Y-%m-%d %H:%M:%S%.9f %z",
+            Self::LongIso => "%Y-%m-%d %H:%M",
+            Self::Iso => "%Y-%m-%d",
+            Self::Custom(format) => format,
+        };
+        match DateTime::from_timestamp(time.unix_seconds(), time.nanoseconds()) {
+            Some(dt) => zone.format(dt, format),
+            None => time.to_string(),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use filetime::FileTime;
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// 以 `O_PATH` 開啟的檔案控制代碼
+///
+/// 只用來讀取與變更時間戳，不會為了讀寫而開啟檔案，因此目錄、FIFO、裝置、
+/// socket 與符號連結本身都能以相同方式處理
+pub struct PathHandle {
+    fd: OwnedFd,
+}
+
+impl PathHandle {
+    /// 開啟路徑；`follow` 為 false 時開啟符號連結本身（`O_NOFOLLOW`），懸空連結也能開啟
+    pub fn open(path: &Path, follow: bool) -> io::Result<Self> {
+        let path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?;
+        let mut flags = libc::O_PATH | libc::O_CLOEXEC;
+        if !follow {
+            flags |= libc::O_NOFOLLOW;
+        }
+        let fd = unsafe { libc::openat(libc::AT_FDCWD, path.as_ptr(), flags) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PathHandle {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// 取得標準輸出所對應檔案的控制代碼，用於 `-` 運算元
+    pub fn stdout() -> io::Result<Self> {
+        let fd = unsafe { libc::fcntl(libc::STDOUT_FILENO, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(PathHandle {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// 讀取目前的存取時間與修改時間
+    pub fn times(&self) -> io::Result<(FileTime, FileTime)> {
+        let mut stat = MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(self.fd.as_raw_fd(), stat.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stat = unsafe { stat.assume_init() };
+        Ok((
+            FileTime::from_unix_time(stat.st_atime, stat.st_atime_nsec as u32),
+            FileTime::from_unix_time(stat.st_mtime, stat.st_mtime_nsec as u32),
+        ))
+    }
+
+    /// 以 `utimensat(fd, "", ..., AT_EMPTY_PATH)` 變更控制代碼所指項目的時間戳
+    ///
+    /// 奈秒數須小於 10 億，否則不呼叫 `utimensat` 而直接回傳錯誤
+    pub fn set_times(&self, atime: FileTime, mtime: FileTime) -> io::Result<()> {
+        for time in [atime, mtime] {
+            if time.nanoseconds() >= 1_000_000_000 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("無效的奈秒數: {}", time.nanoseconds()),
+                ));
+            }
+        }
+        let times = [timespec(atime), timespec(mtime)];
+        let result = unsafe {
+            libc::utimensat(
+                self.fd.as_raw_fd(),
+                c"".as_ptr(),
+                times.as_ptr(),
+                libc::AT_EMPTY_PATH,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+fn timespec(time: FileTime) -> libc::timespec {
+    libc::timespec {
+        tv_sec: time.unix_seconds() as libc::time_t,
+        tv_nsec: time.nanoseconds() as libc::c_long,
+    }
+}
@@ -1,11 +1,21 @@
 use anyhow::Result;
 use clap::Parser;
+use std::process;
 
 mod args;
+mod date;
+mod handle;
+mod manifest;
+mod mode;
+mod rules;
+mod walk;
 use args::Args;
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    args.touch_files()
-}
\ No newline at end of file
+    if !args.touch_files()? {
+        process::exit(1);
+    }
+    Ok(())
+}
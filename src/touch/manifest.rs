@@ -0,0 +1,297 @@
+use anyhow::{Context, Result};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+/// 時間戳清單的檔案格式
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ManifestFormat {
+    Json,
+    Tsv,
+}
+
+impl ManifestFormat {
+    /// 依 --manifest-format 的值決定格式，未指定時依副檔名判斷（.tsv 為 TSV，其餘為 JSON）
+    pub fn detect(path: &Path, explicit: Option<&str>) -> Result<Self> {
+        match explicit {
+            Some("json") => Ok(Self::Json),
+            Some("tsv") => Ok(Self::Tsv),
+            Some(other) => anyhow::bail!("無效的清單格式: {}", other),
+            None => match path.extension().and_then(|ext| ext.to_str()) {
+                Some(ext) if ext.eq_ignore_ascii_case("tsv") => Ok(Self::Tsv),
+                _ => Ok(Self::Json),
+            },
+        }
+    }
+}
+
+/// 以秒與奈秒表示的時間戳
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Timestamp {
+    pub sec: i64,
+    pub nsec: u32,
+}
+
+impl From<FileTime> for Timestamp {
+    fn from(time: FileTime) -> Self {
+        Timestamp {
+            sec: time.unix_seconds(),
+            nsec: time.nanoseconds(),
+        }
+    }
+}
+
+impl From<Timestamp> for FileTime {
+    fn from(time: Timestamp) -> Self {
+        FileTime::from_unix_time(time.sec, time.nsec)
+    }
+}
+
+/// 清單中的一筆記錄
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    pub atime: Timestamp,
+    pub mtime: Timestamp,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonManifest {
+    version: u32,
+    entries: Vec<JsonEntry>,
+}
+
+/// JSON 清單中的一筆記錄；路徑不是 UTF-8 時以 base64 表示原始位元組，
+/// 並由 `encoding` 註明
+#[derive(Serialize, Deserialize)]
+struct JsonEntry {
+    path: String,
+    #[serde(default = "utf8_encoding")]
+    encoding: String,
+    atime: Timestamp,
+    mtime: Timestamp,
+}
+
+impl From<&ManifestEntry> for JsonEntry {
+    fn from(entry: &ManifestEntry) -> Self {
+        let bytes = entry.path.as_os_str().as_bytes();
+        let (path, encoding) = match std::str::from_utf8(bytes) {
+            Ok(path) => (path.to_string(), utf8_encoding()),
+            Err(_) => (BASE64_STANDARD.encode(bytes), "base64".to_string()),
+        };
+        JsonEntry {
+            path,
+            encoding,
+            atime: entry.atime,
+            mtime: entry.mtime,
+        }
+    }
+}
+
+impl TryFrom<JsonEntry> for ManifestEntry {
+    type Error = anyhow::Error;
+
+    fn try_from(entry: JsonEntry) -> Result<Self> {
+        for time in [entry.atime, entry.mtime] {
+            anyhow::ensure!(
+                time.nsec < 1_000_000_000,
+                "無效的時間戳: {}.{}",
+                time.sec,
+                time.nsec
+            );
+        }
+        let path = match entry.encoding.as_str() {
+            "utf-8" => PathBuf::from(entry.path),
+            "base64" => PathBuf::from(OsString::from_vec(
+                BASE64_STANDARD
+                    .decode(&entry.path)
+                    .with_context(|| format!("無效的 base64 路徑: {}", entry.path))?,
+            )),
+            other => anyhow::bail!("無效的路徑編碼: {}", other),
+        };
+        Ok(ManifestEntry {
+            path,
+            atime: entry.atime,
+            mtime: entry.mtime,
+        })
+    }
+}
+
+fn utf8_encoding() -> String {
+    "utf-8".to_string()
+}
+
+/// 將清單寫入檔案，路徑為 `-` 時寫到標準輸出
+pub fn write_manifest(
+    path: &Path,
+    format: ManifestFormat,
+    entries: &[ManifestEntry],
+) -> Result<()> {
+    let writer: Box<dyn Write> = if path == Path::new("-") {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(
+            File::create(path).with_context(|| format!("failed to create manifest {:?}", path))?,
+        )
+    };
+    let mut writer = BufWriter::new(writer);
+
+    match format {
+        ManifestFormat::Json => {
+            let manifest = JsonManifest {
+                version: 1,
+                entries: entries.iter().map(JsonEntry::from).collect(),
+            };
+            serde_json::to_writer_pretty(&mut writer, &manifest)?;
+            writeln!(writer)?;
+        }
+        ManifestFormat::Tsv => {
+            writeln!(writer, "# path\tatime\tmtime")?;
+            for entry in entries {
+                writeln!(
+                    writer,
+                    "{}\t{}.{:09}\t{}.{:09}",
+                    escape_tsv(entry.path.as_os_str().as_bytes()),
+                    entry.atime.sec,
+                    entry.atime.nsec,
+                    entry.mtime.sec,
+                    entry.mtime.nsec
+                )?;
+            }
+        }
+    }
+    writer
+        .flush()
+        .with_context(|| format!("failed to write manifest {:?}", path))
+}
+
+/// 讀取清單，路徑為 `-` 時從標準輸入讀取
+pub fn read_manifest(path: &Path, format: ManifestFormat) -> Result<Vec<ManifestEntry>> {
+    let reader: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(path).with_context(|| {
+            format!("failed to open manifest {:?}", path)
+        })?))
+    };
+
+    match format {
+        ManifestFormat::Json => {
+            let manifest: JsonManifest = serde_json::from_reader(reader)
+                .with_context(|| format!("無法解析時間戳清單: {:?}", path))?;
+            manifest
+                .entries
+                .into_iter()
+                .map(ManifestEntry::try_from)
+                .collect::<Result<_>>()
+                .with_context(|| format!("無法解析時間戳清單: {:?}", path))
+        }
+        ManifestFormat::Tsv => reader
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.as_ref()
+                    .map_or(true, |l| !l.is_empty() && !l.starts_with('#'))
+            })
+            .map(|(index, line)| {
+                parse_tsv_line(&line?)
+                    .with_context(|| format!("無法解析時間戳清單 {:?} 第 {} 行", path, index + 1))
+            })
+            .collect(),
+    }
+}
+
+/// 讀取路徑的時間戳作為清單記錄
+pub fn entry_for(path: &Path, metadata: &fs::Metadata) -> ManifestEntry {
+    ManifestEntry {
+        path: path.to_path_buf(),
+        atime: FileTime::from_last_access_time(metadata).into(),
+        mtime: FileTime::from_last_modification_time(metadata).into(),
+    }
+}
+
+fn parse_tsv_line(line: &str) -> Result<ManifestEntry> {
+    let mut fields = line.rsplitn(3, '\t');
+    let (Some(mtime), Some(atime), Some(path)) = (fields.next(), fields.next(), fields.next())
+    else {
+        anyhow::bail!("欄位數量不足");
+    };
+    Ok(ManifestEntry {
+        path: PathBuf::from(OsString::from_vec(unescape_tsv(path)?)),
+        atime: parse_timestamp(atime)?,
+        mtime: parse_timestamp(mtime)?,
+    })
+}
+
+/// 解析 "秒.奈秒" 格式的時間戳
+fn parse_timestamp(field: &str) -> Result<Timestamp> {
+    let (sec, nsec) = field.split_once('.').unwrap_or((field, "0"));
+    let nsec: u32 = format!("{:0<9}", nsec)
+        .parse()
+        .with_context(|| format!("無效的時間戳: {}", field))?;
+    anyhow::ensure!(nsec < 1_000_000_000, "無效的時間戳: {}", field);
+    Ok(Timestamp {
+        sec: sec
+            .parse()
+            .with_context(|| format!("無效的時間戳: {}", field))?,
+        nsec,
+    })
+}
+
+/// 跳脫反斜線、定位字元與換行，不是 UTF-8 的位元組寫成 `\xNN`；
+/// 開頭的 `#` 寫成 `\x23`，以免被讀取時當成註解
+fn escape_tsv(path: &[u8]) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    let path = match path.strip_prefix(b"#") {
+        Some(rest) => {
+            escaped.push_str("\\x23");
+            rest
+        }
+        None => path,
+    };
+    for chunk in path.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => escaped.push_str("\\\\"),
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                _ => escaped.push(c),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+fn unescape_tsv(field: &str) -> Result<Vec<u8>> {
+    let mut path = Vec::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => path.push(b'\\'),
+            Some('t') => path.push(b'\t'),
+            Some('n') => path.push(b'\n'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .with_context(|| format!("無效的跳脫序列: {}", field))?;
+                path.push(byte);
+            }
+            _ => anyhow::bail!("無效的跳脫序列: {}", field),
+        }
+    }
+    Ok(path)
+}
@@ -0,0 +1,86 @@
+use anyhow::Result;
+
+/// 解析 chmod 風格的權限，支援八進位（如 644）與符號模式（如 u=rw,go=r 或 a+x）
+///
+/// 符號模式以 `base` 為起點計算；未指定 u、g、o、a 時不會變更 `umask` 遮蔽的位元
+pub fn parse_mode(spec: &str, base: u32, umask: u32) -> Result<u32> {
+    if !spec.is_empty() && spec.chars().all(|c| c.is_digit(8)) {
+        let mode = u32::from_str_radix(spec, 8)?;
+        anyhow::ensure!(mode <= 0o7777, "無效的權限模式: {}", spec);
+        return Ok(mode);
+    }
+
+    let mut mode = base;
+    for clause in spec.split(',') {
+        mode = apply_clause(clause, mode, umask)
+            .ok_or_else(|| anyhow::anyhow!("無效的權限模式: {}", spec))?;
+    }
+    Ok(mode)
+}
+
+fn apply_clause(clause: &str, mut mode: u32, umask: u32) -> Option<u32> {
+    let who_end = clause
+        .find(|c| !matches!(c, 'u' | 'g' | 'o' | 'a'))
+        .unwrap_or(clause.len());
+    let (who_str, mut rest) = clause.split_at(who_end);
+
+    let mut who = 0;
+    for c in who_str.chars() {
+        who |= match c {
+            'u' => 0o4700,
+            'g' => 0o2070,
+            'o' => 0o1007,
+            _ => 0o7777,
+        };
+    }
+    // 未指定對象時等同 a，但不影響 umask 遮蔽的位元
+    let mask = if who == 0 { 0o7777 & !umask } else { who };
+    if rest.is_empty() {
+        return None;
+    }
+
+    while let Some(op) = rest.chars().next() {
+        if !matches!(op, '+' | '-' | '=') {
+            return None;
+        }
+        rest = &rest[1..];
+        let perm_end = rest.find(['+', '-', '=']).unwrap_or(rest.len());
+        let (perm_str, next) = rest.split_at(perm_end);
+        rest = next;
+
+        let bits = match perm_str {
+            "u" => spread((mode >> 6) & 0o7),
+            "g" => spread((mode >> 3) & 0o7),
+            "o" => spread(mode & 0o7),
+            _ => {
+                let mut bits = 0;
+                for c in perm_str.chars() {
+                    bits |= match c {
+                        'r' => 0o444,
+                        'w' => 0o222,
+                        'x' => 0o111,
+                        // 只有在已具有任一執行權限時才加入執行權限
+                        'X' if mode & 0o111 != 0 => 0o111,
+                        'X' => 0,
+                        's' => 0o6000,
+                        't' => 0o1000,
+                        _ => return None,
+                    };
+                }
+                bits
+            }
+        };
+
+        mode = match op {
+            '+' => mode | (bits & mask),
+            '-' => mode & !(bits & mask),
+            _ => (mode & !(if who == 0 { 0o7777 } else { who })) | (bits & mask),
+        };
+    }
+    Some(mode)
+}
+
+/// 將三個位元的權限複製到使用者、群組與其他人
+fn spread(bits: u32) -> u32 {
+    (bits << 6) | (bits << 3) | bits
+}
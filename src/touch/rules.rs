@@ -0,0 +1,52 @@
+use anyhow::{Context, Result};
+use filetime::FileTime;
+use glob::{MatchOptions, Pattern};
+use std::fs;
+use std::path::Path;
+
+use crate::date::{self, Zone};
+
+/// 規則檔中的一條規則：符合樣式的項目設為指定時間
+pub struct Rule {
+    pub pattern: Pattern,
+    pub time: FileTime,
+}
+
+/// 讀取規則檔，每行為「萬用字元樣式 時間表示式」，空行與 # 開頭的行會被忽略
+pub fn read_rules(path: &Path, zone: Zone) -> Result<Vec<Rule>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read rules {:?}", path))?;
+
+    let mut rules = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (glob, time) = line
+            .split_once(char::is_whitespace)
+            .with_context(|| format!("規則檔 {:?} 第 {} 行缺少時間", path, index + 1))?;
+        rules.push(Rule {
+            pattern: Pattern::new(glob)
+                .with_context(|| format!("規則檔 {:?} 第 {} 行的樣式無效", path, index + 1))?,
+            time: date::parse_date_string(time.trim(), zone)
+                .with_context(|| format!("規則檔 {:?} 第 {} 行的時間無效", path, index + 1))?,
+        });
+    }
+    Ok(rules)
+}
+
+/// 找出相對路徑適用的時間，多條規則符合時以最後一條為準
+///
+/// `*` 不會跨越目錄，`**` 可符合任意層目錄
+pub fn time_for(rules: &[Rule], relative: &Path) -> Option<FileTime> {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    rules
+        .iter()
+        .rev()
+        .find(|rule| rule.pattern.matches_path_with(relative, options))
+        .map(|rule| rule.time)
+}
@@ -0,0 +1,115 @@
+use glob::Pattern;
+use std::fs;
+use std::path::Path;
+
+use crate::args::io_error_message;
+
+/// 遞迴走訪目錄時的過濾條件
+pub struct TreeFilter {
+    pub include: Vec<Pattern>,
+    pub exclude: Vec<Pattern>,
+    pub max_depth: Option<usize>,
+    pub dirs_only: bool,
+    pub files_only: bool,
+    /// 是否處理目錄內的符號連結本身（-h）；否則略過符號連結
+    pub symlinks: bool,
+}
+
+impl TreeFilter {
+    /// 走訪 `root` 與其下所有項目，對通過過濾條件的項目呼叫 `visit`，
+    /// 並傳入項目的深度（`root` 本身為 0）
+    ///
+    /// 目錄內的符號連結不會被跟隨。回傳值表示走訪與所有 `visit` 是否皆成功
+    pub fn walk(&self, root: &Path, visit: &mut dyn FnMut(&Path, usize) -> bool) -> bool {
+        let is_dir = fs::metadata(root).is_ok_and(|metadata| metadata.is_dir());
+        let mut all_ok = true;
+        if self.accepts(root, Path::new(""), is_dir) {
+            all_ok &= visit(root, 0);
+        }
+        if is_dir {
+            all_ok &= self.walk_dir(root, root, 1, visit);
+        }
+        all_ok
+    }
+
+    fn walk_dir(
+        &self,
+        root: &Path,
+        dir: &Path,
+        depth: usize,
+        visit: &mut dyn FnMut(&Path, usize) -> bool,
+    ) -> bool {
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return true;
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!(
+                    "touch: cannot read directory '{}': {}",
+                    dir.display(),
+                    io_error_message(&e)
+                );
+                return false;
+            }
+        };
+
+        let mut all_ok = true;
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!(
+                        "touch: cannot read directory '{}': {}",
+                        dir.display(),
+                        io_error_message(&e)
+                    );
+                    all_ok = false;
+                    continue;
+                }
+            };
+
+            let path = entry.path();
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            // 被排除的目錄不再往下走訪
+            if self.exclude.iter().any(|p| matches(p, &path, relative)) {
+                continue;
+            }
+
+            // DirEntry::file_type 不會跟隨符號連結
+            let file_type = entry.file_type();
+            if !self.symlinks && file_type.as_ref().is_ok_and(|t| t.is_symlink()) {
+                continue;
+            }
+            let is_dir = file_type.is_ok_and(|t| t.is_dir());
+            if self.accepts(&path, relative, is_dir) {
+                all_ok &= visit(&path, depth);
+            }
+            if is_dir {
+                all_ok &= self.walk_dir(root, &path, depth + 1, visit);
+            }
+        }
+        all_ok
+    }
+
+    fn accepts(&self, path: &Path, relative: &Path, is_dir: bool) -> bool {
+        if (self.dirs_only && !is_dir) || (self.files_only && is_dir) {
+            return false;
+        }
+        if self.exclude.iter().any(|p| matches(p, path, relative)) {
+            return false;
+        }
+        self.include.is_empty() || self.include.iter().any(|p| matches(p, path, relative))
+    }
+}
+
+/// 樣式含 `/` 時比對相對路徑，否則只比對檔名
+fn matches(pattern: &Pattern, path: &Path, relative: &Path) -> bool {
+    if pattern.as_str().contains('/') {
+        pattern.matches_path(relative)
+    } else {
+        path.file_name()
+            .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+    }
+}
@@ -0,0 +1,40 @@
+//! Checks that ls stats each entry once. The count is only reported by a
+//! build with the `stat-count` feature, so this target requires it:
+//! `cargo test --features stat-count --test ls_stat_count_test`.
+
+use assert_cmd::Command;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_ls_stats_each_entry_once() {
+    let stat_calls = |flag: &str, entries: usize| {
+        let dir = tempdir().unwrap();
+        for index in 0..entries {
+            fs::write(dir.path().join(format!("file{:04}", index)), "").unwrap();
+        }
+        let output = Command::cargo_bin("ls")
+            .unwrap()
+            .env("LS_COUNT_STATS", "1")
+            .arg(flag)
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        stderr
+            .trim()
+            .strip_prefix("ls: ")
+            .and_then(|rest| rest.strip_suffix(" stat calls"))
+            .and_then(|count| count.parse::<usize>().ok())
+            .unwrap_or_else(|| panic!("no stat count in {:?}", stderr))
+    };
+
+    // Sorting compares entries O(n log n) times, but each entry is stat'ed
+    // once; the directory itself adds the same few stats at any size
+    for flag in ["-l", "-t", "-S"] {
+        let small = stat_calls(flag, 100);
+        let large = stat_calls(flag, 300);
+        assert_eq!(large - small, 200, "ls {}", flag);
+    }
+}
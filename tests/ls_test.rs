@@ -1,6 +1,8 @@
 use assert_cmd::Command;
+use filetime::FileTime;
 use predicates::prelude::*;
 use std::fs;
+use std::os::unix::fs::{PermissionsExt, symlink};
 use tempfile::tempdir;
 
 #[test]
@@ -41,3 +43,491 @@ fn test_ls_with_path() {
         .success()
         .stdout(predicate::str::contains("test_file"));
 }
+
+fn grid_fixture(names: &[&str]) -> tempfile::TempDir {
+    let dir = tempdir().unwrap();
+    for name in names {
+        fs::write(dir.path().join(name), "").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_ls_columns_and_across() {
+    let dir = grid_fixture(&["a", "bb", "ccc", "dddd", "eeeee"]);
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-C", "-w", "20"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a   ccc   eeeee\nbb  dddd\n");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-x", "-w", "20"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a     bb     ccc\ndddd  eeeee\n");
+}
+
+#[test]
+fn test_ls_one_per_line_when_piped() {
+    let dir = grid_fixture(&["a", "b"]);
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env_remove("COLUMNS")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a\nb\n");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-C", "-1"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a\nb\n");
+}
+
+#[test]
+fn test_ls_columns_use_display_width() {
+    let dir = grid_fixture(&["中文檔名", "ab", "cd"]);
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-x", "-w", "16"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("ab        cd\n中文檔名\n");
+}
+
+#[test]
+fn test_ls_color_from_ls_colors() {
+    let dir = grid_fixture(&["archive.tar", "plain"]);
+    fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("--color=always")
+        .env("LS_COLORS", "di=01;34:*.tar=01;31")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("\x1b[0m\x1b[01;31marchive.tar\x1b[0m\nplain\n\x1b[01;34msub\x1b[0m\n");
+}
+
+#[test]
+fn test_ls_color_auto_and_never_are_plain() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+
+    for flag in ["--color=auto", "--color=never"] {
+        let mut cmd = Command::cargo_bin("ls").unwrap();
+        cmd.arg(flag)
+            .env("LS_COLORS", "di=01;34")
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stdout("sub\n");
+    }
+}
+
+#[test]
+fn test_ls_long_file_types_and_special_bits() {
+    let dir = grid_fixture(&["setuid", "setgid"]);
+    fs::set_permissions(
+        dir.path().join("setuid"),
+        fs::Permissions::from_mode(0o4755),
+    )
+    .unwrap();
+    fs::set_permissions(
+        dir.path().join("setgid"),
+        fs::Permissions::from_mode(0o2644),
+    )
+    .unwrap();
+    fs::create_dir(dir.path().join("sticky")).unwrap();
+    fs::set_permissions(
+        dir.path().join("sticky"),
+        fs::Permissions::from_mode(0o1777),
+    )
+    .unwrap();
+    symlink("setuid", dir.path().join("link")).unwrap();
+    symlink("missing", dir.path().join("broken")).unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("-l")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"(?m)^lrwxrwxrwx .* broken -> missing$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^lrwxrwxrwx .* link -> setuid$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^-rwsr-xr-x .* setuid$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^-rw-r-Sr-- .* setgid$").unwrap())
+        .stdout(predicate::str::is_match(r"(?m)^drwxrwxrwt .* sticky$").unwrap());
+}
+
+#[test]
+fn test_ls_multiple_operands() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("d1")).unwrap();
+    fs::create_dir(dir.path().join("d2")).unwrap();
+    fs::write(dir.path().join("d1/a"), "").unwrap();
+    fs::write(dir.path().join("d2/b"), "").unwrap();
+    fs::write(dir.path().join("zf"), "").unwrap();
+    fs::write(dir.path().join("f1"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["zf", "d2", "f1", "d1"])
+        .assert()
+        .success()
+        .stdout("f1\nzf\n\nd1:\na\n\nd2:\nb\n");
+}
+
+#[test]
+fn test_ls_missing_operand_continues() {
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join("present"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["missing", "present"])
+        .assert()
+        .code(2)
+        .stdout("present\n")
+        .stderr("ls: cannot access 'missing': No such file or directory\n");
+}
+
+#[test]
+fn test_ls_non_utf8_names_are_preserved() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = tempdir().unwrap();
+    fs::write(dir.path().join(OsStr::from_bytes(b"bad\xffname")), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg(dir.path())
+        .assert()
+        .success()
+        .stdout(&b"bad\xffname\n"[..]);
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("-b")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("bad\\377name\n");
+}
+
+#[test]
+fn test_ls_quoting_styles() {
+    let dir = grid_fixture(&["a b", "it's", "new\nline", "plain"]);
+
+    let expected = [
+        ("shell", "'a b'\n\"it's\"\n'new\nline'\nplain\n"),
+        (
+            "shell-escape",
+            "'a b'\n\"it's\"\n'new'$'\\n''line'\nplain\n",
+        ),
+        ("shell-always", "'a b'\n\"it's\"\n'new\nline'\n'plain'\n"),
+        ("c", "\"a b\"\n\"it's\"\n\"new\\nline\"\n\"plain\"\n"),
+        ("escape", "a\\ b\nit's\nnew\\nline\nplain\n"),
+    ];
+    for (style, stdout) in expected {
+        let mut cmd = Command::cargo_bin("ls").unwrap();
+        cmd.env("LC_ALL", "C.UTF-8")
+            .arg(format!("--quoting-style={}", style))
+            .arg(dir.path())
+            .assert()
+            .success()
+            .stdout(stdout);
+    }
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-q", "-N"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a b\nit's\nnew?line\nplain\n");
+}
+
+#[test]
+fn test_ls_version_and_extension_sort() {
+    let dir = grid_fixture(&["file10", "file2", "b.c", "a.txt", "c.txt"]);
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env("LC_ALL", "C")
+        .arg("-v")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("a.txt\nb.c\nc.txt\nfile2\nfile10\n");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env("LC_ALL", "C")
+        .arg("--sort=extension")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("file10\nfile2\nb.c\na.txt\nc.txt\n");
+}
+
+#[test]
+fn test_ls_unsorted_keeps_directory_order() {
+    let dir = tempdir().unwrap();
+    for name in ["b", "c", ".h", "a", "z"] {
+        fs::write(dir.path().join(name), "").unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    let output = cmd.arg("-1aU").arg(dir.path()).output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let listed: Vec<&str> = stdout.lines().collect();
+    // `.` and `..` come from the directory stream like every other entry
+    let mut names: Vec<&str> = listed
+        .iter()
+        .copied()
+        .filter(|name| *name != "." && *name != "..")
+        .collect();
+    assert_eq!(names.len() + 2, listed.len());
+    let read_order: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names, read_order);
+    names.sort();
+    assert_eq!(names, [".h", "a", "b", "c", "z"]);
+}
+
+#[test]
+fn test_ls_group_directories_first() {
+    let dir = grid_fixture(&["a", "c"]);
+    fs::create_dir(dir.path().join("b")).unwrap();
+    fs::create_dir(dir.path().join("d")).unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env("LC_ALL", "C")
+        .args(["--group-directories-first", "-r"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("d\nb\nc\na\n");
+}
+
+#[test]
+fn test_ls_time_selection_and_styles() {
+    let dir = grid_fixture(&["old", "new"]);
+    filetime::set_file_times(
+        dir.path().join("old"),
+        FileTime::from_unix_time(1_700_000_000, 0),
+        FileTime::from_unix_time(1_600_000_000, 500),
+    )
+    .unwrap();
+    filetime::set_file_times(
+        dir.path().join("new"),
+        FileTime::from_unix_time(1_500_000_000, 0),
+        FileTime::from_unix_time(1_650_000_000, 0),
+    )
+    .unwrap();
+
+    // -u without -l sorts by access time, newest first
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("-u")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("old\nnew\n");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["-t", "--full-time"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(
+            "2022-04-15 05:20:00.000000000 \\+0000 new\n.* 2020-09-13 12:26:40.000000500 \\+0000 old\n$",
+        )
+        .unwrap());
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.env("TZ", "UTC")
+        .args(["-lu", "--time-style=iso"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(" 2017-07-14  new\n.* 2023-11-14  old\n$").unwrap());
+}
+
+#[test]
+fn test_ls_size_formatting() {
+    let dir = tempdir().unwrap();
+    for (name, len) in [("a", 1025), ("b", 10239), ("c", 999)] {
+        fs::File::create(dir.path().join(name))
+            .unwrap()
+            .set_len(len)
+            .unwrap();
+    }
+
+    // Human-readable sizes round up and keep one decimal only below 10
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-l", "-H"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(" 1.1K .* a\n.*  10K .* b\n.*  999 .* c\n$").unwrap());
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-l", "--si"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(" 1.1k .* a\n.*  11k .* b\n.*  999 .* c\n$").unwrap());
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-l", "--block-size=KB"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(
+            predicate::str::is_match("^total \\d+kB\n.* 2kB .* a\n.* 11kB .* b\n.*  1kB .* c\n$")
+                .unwrap(),
+        );
+
+    // Sparse files have no blocks allocated
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.args(["-s", "-1"])
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout("total 0\n0 a\n0 b\n0 c\n");
+}
+
+#[test]
+fn test_ls_tree() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("a/b")).unwrap();
+    fs::write(dir.path().join("a/b/deep"), "").unwrap();
+    fs::write(dir.path().join("a/x"), "").unwrap();
+    fs::write(dir.path().join("z"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("--tree")
+        .assert()
+        .success()
+        .stdout(
+            ".\n├── a\n│   ├── b\n│   │   └── deep\n│   └── x\n└── z\n\n2 directories, 3 files\n",
+        );
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["--tree", "--level=2", "--charset=ascii", "-r"])
+        .assert()
+        .success()
+        .stdout(".\n|-- z\n`-- a\n    |-- x\n    `-- b\n\n2 directories, 2 files\n");
+
+    // A link to a directory counts as a directory but is not descended into
+    symlink("a", dir.path().join("link")).unwrap();
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["--tree", "--level=1", "--charset=ascii"])
+        .assert()
+        .success()
+        .stdout(".\n|-- a\n|-- link\n`-- z\n\n2 directories, 1 file\n");
+
+    // File operands alone draw no tree and get no summary
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["--tree", "z"])
+        .assert()
+        .success()
+        .stdout("z\n");
+}
+
+#[test]
+fn test_ls_machine_readable_formats() {
+    let dir = grid_fixture(&["a,b"]);
+    fs::write(dir.path().join("data"), "12345").unwrap();
+    symlink("data", dir.path().join("link")).unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    let output = cmd.arg("--format=json").arg(dir.path()).output().unwrap();
+    assert!(output.status.success());
+    let records: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let records = records.as_array().unwrap();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[1]["name"], "data");
+    assert_eq!(records[1]["type"], "file");
+    assert_eq!(records[1]["size"], 5);
+    assert_eq!(records[1]["mode_symbolic"].as_str().unwrap().len(), 10);
+    assert!(records[1]["mtime"]["nsec"].is_i64());
+    assert!(records[1]["target"].is_null());
+    assert_eq!(records[2]["type"], "symlink");
+    assert_eq!(records[2]["target"], "data");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("--format=ndjson")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::function(|out: &str| {
+            out.lines()
+                .all(|line| serde_json::from_str::<serde_json::Value>(line).is_ok())
+                && out.lines().count() == 3
+        }));
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.arg("--format=csv")
+        .arg(dir.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("name,path,encoding,type,"))
+        .stdout(predicate::str::contains("\n\"a,b\","));
+}
+
+#[test]
+fn test_ls_recursive_hidden_and_depth() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join(".hidden/inner")).unwrap();
+    fs::write(dir.path().join(".hidden/inner/file"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("-Ra")
+        .assert()
+        .success()
+        .stdout(
+            ".:\n.\n..\n.hidden\n\n./.hidden:\n.\n..\ninner\n\n./.hidden/inner:\n.\n..\nfile\n",
+        );
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["-Ra", "--max-depth=1"])
+        .assert()
+        .success()
+        .stdout(".:\n.\n..\n.hidden\n\n./.hidden:\n.\n..\ninner\n");
+}
+
+#[test]
+fn test_ls_recursive_symlink_loop() {
+    let dir = tempdir().unwrap();
+    fs::create_dir(dir.path().join("a")).unwrap();
+    symlink("..", dir.path().join("a/up")).unwrap();
+
+    // Without -L the link is not followed
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("-R")
+        .assert()
+        .success()
+        .stdout(".:\na\n\n./a:\nup\n");
+
+    let mut cmd = Command::cargo_bin("ls").unwrap();
+    cmd.current_dir(dir.path())
+        .arg("-RL")
+        .assert()
+        .code(2)
+        .stdout(".:\na\n\n./a:\nup\n")
+        .stderr("ls: ./a/up: not listing already-listed directory\n");
+}
@@ -1,6 +1,10 @@
 use assert_cmd::Command;
+use filetime::FileTime;
+use predicates::prelude::*;
+use tempfile::tempdir;
 
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 
 #[test]
 fn test_touch_creates_file() {
@@ -108,3 +112,869 @@ fn test_touch_time_format() {
     assert_eq!(dt.format("%Y%m%d%H%M.%S").to_string(), "202301011200.00");
     fs::remove_file("test_file_t").unwrap();
 }
+
+#[test]
+fn test_touch_continues_after_failure() {
+    let dir = tempdir().unwrap();
+    let bad = dir.path().join("missing_dir").join("file");
+    let good = dir.path().join("good_file");
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg(&bad)
+        .arg(&good)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "touch: cannot touch '{}': No such file or directory",
+            bad.display()
+        )));
+    assert!(fs::metadata(&good).is_ok());
+}
+
+#[test]
+fn test_touch_no_create_with_access_only() {
+    let dir = tempdir().unwrap();
+    let missing = dir.path().join("missing");
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-c", "-a"])
+        .arg(&missing)
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+    assert!(fs::metadata(&missing).is_err());
+}
+
+#[test]
+fn test_touch_no_create_reports_other_errors() {
+    let dir = tempdir().unwrap();
+    let looping = dir.path().join("loop");
+    std::os::unix::fs::symlink(&looping, &looping).unwrap();
+    let file = dir.path().join("regular_file");
+    fs::write(&file, "").unwrap();
+    let below_file = file.join("x");
+
+    // -c 只略過不存在的檔案，其他錯誤仍會回報
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-c")
+        .arg(&looping)
+        .arg(&below_file)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "touch: setting times of '{}': Too many levels of symbolic links",
+            looping.display()
+        )))
+        .stderr(predicate::str::contains(format!(
+            "touch: setting times of '{}': Not a directory",
+            below_file.display()
+        )));
+}
+
+#[test]
+fn test_touch_modify_only_creates_file() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("new_file");
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-m").arg(&file).assert().success();
+    assert!(fs::metadata(&file).is_ok());
+}
+
+#[test]
+fn test_touch_reference_with_relative_date() {
+    let dir = tempdir().unwrap();
+    let reference = dir.path().join("ref");
+    let target = dir.path().join("target");
+    fs::write(&reference, "").unwrap();
+    let ref_atime = FileTime::from_unix_time(1_600_000_000, 0);
+    let ref_mtime = FileTime::from_unix_time(1_650_000_000, 0);
+    filetime::set_file_times(&reference, ref_atime, ref_mtime).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-r")
+        .arg(&reference)
+        .arg("-d")
+        .arg("+2 hours")
+        .arg(&target)
+        .assert()
+        .success();
+    let meta = fs::metadata(&target).unwrap();
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).unix_seconds(),
+        1_600_000_000 + 7200
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta).unix_seconds(),
+        1_650_000_000 + 7200
+    );
+}
+
+#[test]
+fn test_touch_calendar_offsets() {
+    let dir = tempdir().unwrap();
+    let reference = dir.path().join("ref");
+    let target = dir.path().join("target");
+    fs::write(&reference, "").unwrap();
+    // 2020-02-29 與 2023-01-31（UTC）
+    filetime::set_file_times(
+        &reference,
+        FileTime::from_unix_time(1_582_934_400, 0),
+        FileTime::from_unix_time(1_675_123_200, 0),
+    )
+    .unwrap();
+
+    // 月份與年依日曆計算，超出該月天數時取月底
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-u")
+        .arg("-r")
+        .arg(&reference)
+        .args(["-d", "+1 year 1 month"])
+        .arg(&target)
+        .assert()
+        .success();
+    let meta = fs::metadata(&target).unwrap();
+    // 2021-03-29 與 2024-02-29
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).unix_seconds(),
+        1_616_976_000
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta).unix_seconds(),
+        1_709_164_800
+    );
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-u", "--shift=1 month ago"])
+        .arg(&target)
+        .assert()
+        .success();
+    let meta = fs::metadata(&target).unwrap();
+    // 2021-02-28 與 2024-01-29
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).unix_seconds(),
+        1_614_470_400
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta).unix_seconds(),
+        1_706_486_400
+    );
+}
+
+#[test]
+fn test_touch_shift() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("skewed");
+    fs::write(&file, "").unwrap();
+    filetime::set_file_times(
+        &file,
+        FileTime::from_unix_time(1_700_000_000, 0),
+        FileTime::from_unix_time(1_700_100_000, 0),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("--shift=-1 day 30 minutes")
+        .arg(&file)
+        .assert()
+        .success();
+    let meta = fs::metadata(&file).unwrap();
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).unix_seconds(),
+        1_700_000_000 - 86400 + 1800
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta).unix_seconds(),
+        1_700_100_000 - 86400 + 1800
+    );
+}
+
+#[test]
+fn test_touch_offset_overflow_is_rejected() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("file");
+    let offset = "9223372036854775 s 9223372036854775 s";
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg(format!("--shift={}", offset))
+        .arg(&file)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("無效的時間位移"));
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-d", offset])
+        .arg(&file)
+        .assert()
+        .failure()
+        .code(1);
+    assert!(fs::metadata(&file).is_err());
+}
+
+#[test]
+fn test_touch_recursive_with_filters() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("tree");
+    fs::create_dir_all(root.join("src/nested/deep")).unwrap();
+    fs::create_dir_all(root.join("target")).unwrap();
+    for file in [
+        "src/lib.rs",
+        "src/notes.txt",
+        "src/nested/mod.rs",
+        "src/nested/deep/inner.rs",
+        "target/out.rs",
+    ] {
+        fs::write(root.join(file), "").unwrap();
+    }
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args([
+        "-R",
+        "--include",
+        "*.rs",
+        "--exclude",
+        "target",
+        "--max-depth",
+        "3",
+    ])
+    .args(["-d", "2001-02-03 04:05:06"])
+    .arg(&root)
+    .assert()
+    .success();
+
+    let mtime_year = |file: &str| {
+        let mtime = fs::metadata(root.join(file)).unwrap().modified().unwrap();
+        let dt: chrono::DateTime<chrono::Local> = mtime.into();
+        dt.format("%Y").to_string()
+    };
+    assert_eq!(mtime_year("src/lib.rs"), "2001");
+    assert_eq!(mtime_year("src/nested/mod.rs"), "2001");
+    assert_ne!(mtime_year("src/nested/deep/inner.rs"), "2001");
+    assert_ne!(mtime_year("src/notes.txt"), "2001");
+    assert_ne!(mtime_year("target/out.rs"), "2001");
+    assert_ne!(mtime_year("src"), "2001");
+}
+
+#[test]
+fn test_touch_recursive_dirs_only() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("tree");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("sub/file"), "").unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-R", "--dirs-only", "-d", "2001-02-03 04:05:06"])
+        .arg(&root)
+        .assert()
+        .success();
+
+    let mtime = |path: &std::path::Path| {
+        FileTime::from_last_modification_time(&fs::metadata(path).unwrap())
+    };
+    assert_eq!(mtime(&root), mtime(&root.join("sub")));
+    assert_ne!(mtime(&root), mtime(&root.join("sub/file")));
+}
+
+#[test]
+fn test_touch_recursive_does_not_follow_symlinks() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("tree");
+    let outside = dir.path().join("outside");
+    fs::create_dir_all(&root).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(root.join("file"), "").unwrap();
+    fs::write(outside.join("secret"), "").unwrap();
+    std::os::unix::fs::symlink("../outside/secret", root.join("lnk")).unwrap();
+    let secret_mtime =
+        || FileTime::from_last_modification_time(&fs::metadata(outside.join("secret")).unwrap());
+    let before = secret_mtime();
+
+    // 目錄內的符號連結保持不變，也不會變更連結所指的檔案
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-R", "-d", "@0"]).arg(&root).assert().success();
+    let mtime = |path: &std::path::Path| {
+        FileTime::from_last_modification_time(&fs::symlink_metadata(path).unwrap()).unix_seconds()
+    };
+    assert_eq!(mtime(&root.join("file")), 0);
+    assert_ne!(mtime(&root.join("lnk")), 0);
+    assert_eq!(secret_mtime(), before);
+
+    // -h 時變更符號連結本身
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-R", "-h", "-d", "@0"])
+        .arg(&root)
+        .assert()
+        .success();
+    assert_eq!(mtime(&root.join("lnk")), 0);
+    assert_eq!(secret_mtime(), before);
+}
+
+fn manifest_round_trip(manifest_name: &str) {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("artifacts");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    let file = root.join("sub/with\ttab.o");
+    fs::write(&file, "").unwrap();
+    let atime = FileTime::from_unix_time(1_600_000_000, 123_456_789);
+    let mtime = FileTime::from_unix_time(1_650_000_000, 987_654_321);
+    filetime::set_file_times(&file, atime, mtime).unwrap();
+
+    let manifest = dir.path().join(manifest_name);
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("--dump-manifest")
+        .arg(&manifest)
+        .arg(&root)
+        .assert()
+        .success();
+
+    filetime::set_file_times(&file, FileTime::now(), FileTime::now()).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("--from-manifest").arg(&manifest).assert().success();
+    let meta = fs::metadata(&file).unwrap();
+    assert_eq!(FileTime::from_last_access_time(&meta), atime);
+    assert_eq!(FileTime::from_last_modification_time(&meta), mtime);
+}
+
+#[test]
+fn test_touch_manifest_json_round_trip() {
+    manifest_round_trip("manifest.json");
+}
+
+#[test]
+fn test_touch_manifest_tsv_round_trip() {
+    manifest_round_trip("manifest.tsv");
+}
+
+#[test]
+fn test_touch_manifest_leading_hash() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("#hash");
+    fs::write(&file, "").unwrap();
+    let mtime = FileTime::from_unix_time(1_650_000_000, 0);
+    filetime::set_file_times(&file, mtime, mtime).unwrap();
+
+    // 開頭為 # 的路徑不會被當成註解行
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["--dump-manifest", "m.tsv", "#hash"])
+        .assert()
+        .success();
+    filetime::set_file_times(&file, FileTime::now(), FileTime::now()).unwrap();
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.current_dir(dir.path())
+        .args(["--from-manifest", "m.tsv"])
+        .assert()
+        .success();
+    assert_eq!(
+        FileTime::from_last_modification_time(&fs::metadata(&file).unwrap()),
+        mtime
+    );
+}
+
+#[test]
+fn test_touch_manifest_rejects_invalid_nanoseconds() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("file");
+    fs::write(&file, "").unwrap();
+    let manifest = dir.path().join("m.json");
+    fs::write(
+        &manifest,
+        format!(
+            r#"{{"version":1,"entries":[{{"path":{:?},"atime":{{"sec":1,"nsec":2000000000}},"mtime":{{"sec":1,"nsec":0}}}}]}}"#,
+            file.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("--from-manifest")
+        .arg(&manifest)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("無效的時間戳: 1.2000000000"));
+}
+
+#[test]
+fn test_touch_manifest_non_utf8_path() {
+    use std::os::unix::ffi::OsStrExt;
+
+    for manifest_name in ["manifest.json", "manifest.tsv"] {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("artifacts");
+        fs::create_dir_all(&root).unwrap();
+        let file = root.join(std::ffi::OsStr::from_bytes(b"bad\xff"));
+        fs::write(&file, "").unwrap();
+        let mtime = FileTime::from_unix_time(1_650_000_000, 987_654_321);
+        filetime::set_file_times(&file, mtime, mtime).unwrap();
+
+        let manifest = dir.path().join(manifest_name);
+        let mut cmd = Command::cargo_bin("touch").unwrap();
+        cmd.arg("--dump-manifest")
+            .arg(&manifest)
+            .arg(&root)
+            .assert()
+            .success();
+
+        filetime::set_file_times(&file, FileTime::now(), FileTime::now()).unwrap();
+        let mut cmd = Command::cargo_bin("touch").unwrap();
+        cmd.arg("--from-manifest").arg(&manifest).assert().success();
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(&file).unwrap()),
+            mtime
+        );
+
+        // 清單中已不存在的檔案會回報錯誤，且不會被建立
+        fs::remove_file(&file).unwrap();
+        let mut cmd = Command::cargo_bin("touch").unwrap();
+        cmd.arg("--from-manifest")
+            .arg(&manifest)
+            .assert()
+            .failure()
+            .code(1)
+            .stderr(predicate::str::contains("No such file or directory"));
+        assert!(fs::symlink_metadata(&file).is_err());
+    }
+}
+
+#[test]
+fn test_touch_reproducible_clamp() {
+    let dir = tempdir().unwrap();
+    let old = dir.path().join("old");
+    let new = dir.path().join("new");
+    fs::write(&old, "").unwrap();
+    fs::write(&new, "").unwrap();
+    let old_time = FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_times(&old, old_time, old_time).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.env("SOURCE_DATE_EPOCH", "1500000000")
+        .args(["--reproducible", "--clamp"])
+        .arg(&old)
+        .arg(&new)
+        .assert()
+        .success();
+
+    let old_meta = fs::metadata(&old).unwrap();
+    assert_eq!(FileTime::from_last_modification_time(&old_meta), old_time);
+    assert_eq!(FileTime::from_last_access_time(&old_meta), old_time);
+    let new_meta = fs::metadata(&new).unwrap();
+    assert_eq!(
+        FileTime::from_last_modification_time(&new_meta),
+        FileTime::from_unix_time(1_500_000_000, 0)
+    );
+}
+
+#[test]
+fn test_touch_reproducible_requires_epoch() {
+    let dir = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.env_remove("SOURCE_DATE_EPOCH")
+        .arg("--reproducible")
+        .arg(dir.path().join("file"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("SOURCE_DATE_EPOCH"));
+}
+
+#[test]
+fn test_touch_if_older_than_reference() {
+    let dir = tempdir().unwrap();
+    let reference = dir.path().join("source.c");
+    let stale = dir.path().join("stale.stamp");
+    let fresh = dir.path().join("fresh.stamp");
+    for file in [&reference, &stale, &fresh] {
+        fs::write(file, "").unwrap();
+    }
+    let set_mtime = |path: &std::path::Path, seconds| {
+        let time = FileTime::from_unix_time(seconds, 0);
+        filetime::set_file_times(path, time, time).unwrap();
+    };
+    set_mtime(&reference, 1_500_000_000);
+    set_mtime(&stale, 1_400_000_000);
+    set_mtime(&fresh, 1_600_000_000);
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-v")
+        .arg("--if-older-than")
+        .arg(&reference)
+        .args(["-d", "2020-01-01 00:00:00"])
+        .arg(&stale)
+        .arg(&fresh)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "skipped '{}': modification time",
+            fresh.display()
+        )))
+        .stdout(predicate::str::contains(format!("skipped '{}'", stale.display())).not());
+
+    let mtime = |path: &std::path::Path| {
+        FileTime::from_last_modification_time(&fs::metadata(path).unwrap()).unix_seconds()
+    };
+    assert_ne!(mtime(&stale), 1_400_000_000);
+    assert_eq!(mtime(&fresh), 1_600_000_000);
+}
+
+#[test]
+fn test_touch_if_newer_than_date() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("old");
+    fs::write(&file, "").unwrap();
+    let old_time = FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_times(&file, old_time, old_time).unwrap();
+    let missing = dir.path().join("missing");
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["--if-newer-than", "2010-01-01 00:00:00"])
+        .arg(&file)
+        .arg(&missing)
+        .assert()
+        .success();
+    assert_eq!(
+        FileTime::from_last_modification_time(&fs::metadata(&file).unwrap()),
+        old_time
+    );
+    assert!(fs::metadata(&missing).is_err());
+}
+
+#[test]
+fn test_touch_parents_mode_and_template() {
+    let dir = tempdir().unwrap();
+    let template = dir.path().join("template.txt");
+    fs::write(&template, "placeholder\n").unwrap();
+    let file = dir.path().join("a/b/c/placeholder.txt");
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-p", "--mode", "u=rw,g=r,o="])
+        .arg("--template")
+        .arg(&template)
+        .arg(&file)
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&file).unwrap(), "placeholder\n");
+    let mode = fs::metadata(&file).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o640);
+}
+
+#[test]
+fn test_touch_mode_only_applies_to_new_files() {
+    let dir = tempdir().unwrap();
+    let existing = dir.path().join("existing");
+    let created = dir.path().join("created");
+    fs::write(&existing, "").unwrap();
+    fs::set_permissions(&existing, fs::Permissions::from_mode(0o600)).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["--mode", "755"])
+        .arg(&existing)
+        .arg(&created)
+        .assert()
+        .success();
+
+    let mode = |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+    assert_eq!(mode(&existing), 0o600);
+    assert_eq!(mode(&created), 0o755);
+}
+
+#[test]
+fn test_touch_dry_run() {
+    let dir = tempdir().unwrap();
+    let existing = dir.path().join("existing");
+    let missing = dir.path().join("missing");
+    fs::write(&existing, "").unwrap();
+    let old_time = FileTime::from_unix_time(1_000_000_000, 0);
+    filetime::set_file_times(&existing, old_time, old_time).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["-n", "-m", "--time-style", "+%Y"])
+        .args(["-d", "2021-06-01 00:00:00"])
+        .arg(&existing)
+        .arg(&missing)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "would update '{}': atime unchanged, mtime 2001 -> 2021",
+            existing.display()
+        )))
+        .stdout(predicate::str::contains(format!(
+            "would create '{}'",
+            missing.display()
+        )));
+
+    let meta = fs::metadata(&existing).unwrap();
+    assert_eq!(FileTime::from_last_modification_time(&meta), old_time);
+    assert!(fs::metadata(&missing).is_err());
+}
+
+#[test]
+fn test_touch_explicit_time_zone() {
+    let dir = tempdir().unwrap();
+    let taipei = dir.path().join("taipei");
+    let utc = dir.path().join("utc");
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.env("TZ", "America/New_York")
+        .args(["--tz", "Asia/Taipei", "-d", "2024-01-01 08:00:00"])
+        .arg(&taipei)
+        .assert()
+        .success();
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.env("TZ", "America/New_York")
+        .args(["-u", "-t", "202401010000.00"])
+        .arg(&utc)
+        .assert()
+        .success();
+
+    let mtime = |path: &std::path::Path| {
+        FileTime::from_last_modification_time(&fs::metadata(path).unwrap()).unix_seconds()
+    };
+    assert_eq!(mtime(&taipei), 1_704_067_200);
+    assert_eq!(mtime(&utc), 1_704_067_200);
+}
+
+#[test]
+fn test_touch_invalid_time_zone() {
+    let dir = tempdir().unwrap();
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["--tz", "Mars/Olympus", "-d", "2024-01-01 00:00:00"])
+        .arg(dir.path().join("file"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Mars/Olympus"));
+}
+
+#[test]
+fn test_touch_subsecond_precision() {
+    let dir = tempdir().unwrap();
+    let cases = [
+        (
+            vec!["-u", "-t", "202301011230.30.123456789"],
+            1_672_576_230,
+            123_456_789,
+        ),
+        (
+            vec!["-d", "@1700000000.123456789"],
+            1_700_000_000,
+            123_456_789,
+        ),
+        (vec!["-d", "@-86400.5"], -86_401, 500_000_000),
+        (
+            vec!["-u", "-d", "2100-01-01 00:00:00.25"],
+            4_102_444_800,
+            250_000_000,
+        ),
+    ];
+    for (index, (args, seconds, nanos)) in cases.into_iter().enumerate() {
+        let file = dir.path().join(format!("file{}", index));
+        let mut cmd = Command::cargo_bin("touch").unwrap();
+        cmd.args(&args).arg(&file).assert().success();
+        let mtime = FileTime::from_last_modification_time(&fs::metadata(&file).unwrap());
+        assert_eq!(mtime.unix_seconds(), seconds, "{:?}", args);
+        assert_eq!(mtime.nanoseconds(), nanos, "{:?}", args);
+    }
+}
+
+#[test]
+fn test_touch_reference_preserves_nanoseconds() {
+    let dir = tempdir().unwrap();
+    let reference = dir.path().join("ref");
+    let target = dir.path().join("target");
+    fs::write(&reference, "").unwrap();
+    let time = FileTime::from_unix_time(-1_000_000, 987_654_321);
+    filetime::set_file_times(&reference, time, time).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-r")
+        .arg(&reference)
+        .arg(&target)
+        .assert()
+        .success();
+    let meta = fs::metadata(&target).unwrap();
+    assert_eq!(FileTime::from_last_modification_time(&meta), time);
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).nanoseconds(),
+        987_654_321
+    );
+}
+
+#[test]
+fn test_touch_independent_atime_and_mtime() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("fixture");
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["--atime", "@1000000000.5", "--mtime", "@1500000000.25"])
+        .arg(&file)
+        .assert()
+        .success();
+    let meta = fs::metadata(&file).unwrap();
+    assert_eq!(
+        FileTime::from_last_access_time(&meta),
+        FileTime::from_unix_time(1_000_000_000, 500_000_000)
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta),
+        FileTime::from_unix_time(1_500_000_000, 250_000_000)
+    );
+
+    // 只指定 --atime 時修改時間保持不變
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.args(["--atime", "@1200000000"])
+        .arg(&file)
+        .assert()
+        .success();
+    let meta = fs::metadata(&file).unwrap();
+    assert_eq!(
+        FileTime::from_last_access_time(&meta).unix_seconds(),
+        1_200_000_000
+    );
+    assert_eq!(
+        FileTime::from_last_modification_time(&meta),
+        FileTime::from_unix_time(1_500_000_000, 250_000_000)
+    );
+}
+
+#[test]
+fn test_touch_copy_mtime_into_atime() {
+    let dir = tempdir().unwrap();
+    let file = dir.path().join("fixture");
+    fs::write(&file, "").unwrap();
+    let mtime = FileTime::from_unix_time(1_300_000_000, 123);
+    filetime::set_file_times(&file, FileTime::from_unix_time(1_000_000_000, 0), mtime).unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("--from-mtime").arg(&file).assert().success();
+    let meta = fs::metadata(&file).unwrap();
+    assert_eq!(FileTime::from_last_access_time(&meta), mtime);
+    assert_eq!(FileTime::from_last_modification_time(&meta), mtime);
+}
+
+/// 以固定時間 touch 指定路徑，並回傳不跟隨符號連結所讀到的修改時間
+fn touch_special(path: &std::path::Path, extra_args: &[&str]) -> FileTime {
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.timeout(std::time::Duration::from_secs(5))
+        .args(extra_args)
+        .args(["-d", "@1234567890.5"])
+        .arg(path)
+        .assert()
+        .success();
+    FileTime::from_last_modification_time(&fs::symlink_metadata(path).unwrap())
+}
+
+#[test]
+fn test_touch_directory() {
+    let dir = tempdir().unwrap();
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    assert_eq!(
+        touch_special(&sub, &[]),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+}
+
+#[test]
+fn test_touch_fifo_does_not_block() {
+    let dir = tempdir().unwrap();
+    let fifo = dir.path().join("fifo");
+    let c_path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o644) }, 0);
+    assert_eq!(
+        touch_special(&fifo, &[]),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+}
+
+#[test]
+fn test_touch_socket() {
+    let dir = tempdir().unwrap();
+    let socket = dir.path().join("socket");
+    let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+    assert_eq!(
+        touch_special(&socket, &[]),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+}
+
+#[test]
+fn test_touch_device_node() {
+    let dir = tempdir().unwrap();
+    let device = dir.path().join("null");
+    let c_path = std::ffi::CString::new(device.to_str().unwrap()).unwrap();
+    // 建立裝置節點需要 CAP_MKNOD，沒有權限時略過
+    if unsafe { libc::mknod(c_path.as_ptr(), libc::S_IFCHR | 0o666, libc::makedev(1, 3)) } != 0 {
+        return;
+    }
+    assert_eq!(
+        touch_special(&device, &[]),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+}
+
+#[test]
+fn test_touch_dangling_symlink() {
+    let dir = tempdir().unwrap();
+    let link = dir.path().join("link");
+    let target = dir.path().join("target");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    // -h 變更符號連結本身，不會建立目的地
+    assert_eq!(
+        touch_special(&link, &["-h"]),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+    assert!(fs::symlink_metadata(&target).is_err());
+
+    // 未使用 -h 時會經由連結建立目的地
+    touch_special(&link, &[]);
+    assert_eq!(
+        FileTime::from_last_modification_time(&fs::metadata(&target).unwrap()),
+        FileTime::from_unix_time(1_234_567_890, 500_000_000)
+    );
+}
+
+#[test]
+fn test_touch_rules_file() {
+    let dir = tempdir().unwrap();
+    let root = dir.path().join("release");
+    fs::create_dir_all(root.join("src/nested")).unwrap();
+    fs::create_dir_all(root.join("docs/guide")).unwrap();
+    for file in [
+        "src/main.rs",
+        "src/nested/lib.rs",
+        "src/notes.txt",
+        "docs/guide/index.md",
+        "README.md",
+    ] {
+        fs::write(root.join(file), "").unwrap();
+    }
+    let rules = dir.path().join("stamps.rules");
+    fs::write(
+        &rules,
+        "# 發行版本的時間戳\n\
+         src/**/*.rs  @1700000000\n\
+         docs/**      2024-01-01\n\
+         docs/guide/index.md  @1600000000\n",
+    )
+    .unwrap();
+
+    let mut cmd = Command::cargo_bin("touch").unwrap();
+    cmd.arg("-u")
+        .arg("--rules")
+        .arg(&rules)
+        .arg(&root)
+        .assert()
+        .success();
+
+    let mtime = |file: &str| {
+        FileTime::from_last_modification_time(&fs::metadata(root.join(file)).unwrap())
+            .unix_seconds()
+    };
+    assert_eq!(mtime("src/main.rs"), 1_700_000_000);
+    assert_eq!(mtime("src/nested/lib.rs"), 1_700_000_000);
+    assert_eq!(mtime("docs/guide"), 1_704_067_200);
+    assert_eq!(mtime("docs/guide/index.md"), 1_600_000_000);
+    assert_ne!(mtime("src/notes.txt"), 1_700_000_000);
+    assert_ne!(mtime("README.md"), 1_700_000_000);
+}